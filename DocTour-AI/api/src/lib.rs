@@ -1,11 +1,17 @@
+use system::metrics::{Registry, CONTENT_TYPE};
+
 pub fn add(left: usize, right: usize) -> usize {
     left + right
 }
 
+/// Content type and body of the `/metrics` response, for Prometheus to scrape.
+pub fn metrics() -> (&'static str, String) {
+    (CONTENT_TYPE, Registry::open().render())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use system::Logger;
     use system::benchmark;
 
     #[test]
@@ -17,7 +23,16 @@ mod tests {
     #[test]
     fn test_system() {
         let result = benchmark!(add(2, 2));
-        Logger::error("Mock Error", false);
+        system::log!(Error, "Mock Error", false);
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn test_metrics() {
+        Registry::open().counter("api_test_requests_total", &[("route", "/metrics")]).inc();
+
+        let (content_type, body) = metrics();
+        assert!(content_type.starts_with("text/plain"));
+        assert!(body.contains("api_test_requests_total{route=\"/metrics\"} 1"));
+    }
 }
@@ -0,0 +1,13 @@
+use std::process::ExitCode;
+
+use doctour_ai::schemas::{typescript, Registry};
+
+/// Exports the schemas registered by `doctour_ai`, see `typescript::command`. The registry only holds the
+/// schemas of the crates linked into the binary, so crates declaring their own have their own `schemas` binary
+/// calling `typescript::command`.
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    typescript::command(&args, Registry::open())
+}
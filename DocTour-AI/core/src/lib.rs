@@ -1 +1,3 @@
-mod schemas;
+extern crate self as doctour_ai; // So #[derive(Schema)] can be used inside this crate too
+
+pub mod schemas;
@@ -0,0 +1,210 @@
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+use serde_json::{Error as JsonError, Value};
+
+use super::validation::{pointer, Violation};
+
+/// What went wrong, for machines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The document isn't JSON.
+    Syntax,
+    MissingField,
+    /// A field a strict schema doesn't have.
+    UnknownField,
+    /// A value of the wrong type, e.g. a string for a number.
+    InvalidType,
+    /// A value of the right type that isn't accepted, e.g. an unknown variant.
+    InvalidValue,
+    /// A value breaking a validation rule.
+    Validation,
+    /// A document of another version that couldn't be upgraded.
+    Migration,
+}
+
+/// An error decoding a document as a schema, ready to be returned to API clients (as a 422).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaError {
+    /// Name of the schema, e.g. `ChatRequest`.
+    pub schema: String,
+    /// JSON pointer of the failing value, e.g. `/messages/0/role`. Empty for the whole document.
+    pub path: String,
+    pub kind: ErrorKind,
+    /// JSON type expected at `path`, e.g. `integer`, when it is known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    /// Broken rule of a `Validation` error, e.g. `range`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    pub message: String,
+}
+
+impl SchemaError {
+    /// Error of `serde_json`, found at `path` while decoding the schema `name` described by `schema`.
+    pub(crate) fn json(name: &str, schema: &Value, path: &serde_path_to_error::Path, error: &JsonError) -> SchemaError {
+        let mut pointer_path = String::new();
+        for segment in path {
+            match segment {
+                serde_path_to_error::Segment::Seq { index } => pointer_path = pointer(&pointer_path, index),
+                serde_path_to_error::Segment::Map { key } => pointer_path = pointer(&pointer_path, key),
+                serde_path_to_error::Segment::Enum { variant } => pointer_path = pointer(&pointer_path, variant),
+                serde_path_to_error::Segment::Unknown => {}
+            }
+        }
+
+        // serde_json's message, without its position
+        let message = error.to_string();
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+        let field = message.split('`').nth(1).unwrap_or_default();
+
+        let kind = if !error.is_data() {
+            ErrorKind::Syntax
+        } else if message.starts_with("missing field") {
+            pointer_path = pointer(&pointer_path, field);
+            ErrorKind::MissingField
+        } else if message.starts_with("unknown field") { // Already in the path
+            ErrorKind::UnknownField
+        } else if message.starts_with("invalid type") {
+            ErrorKind::InvalidType
+        } else {
+            ErrorKind::InvalidValue
+        };
+
+        let expected = match kind {
+            ErrorKind::MissingField | ErrorKind::InvalidType | ErrorKind::InvalidValue => expected_type(schema, &pointer_path)
+                .or_else(|| message.rsplit_once(", expected ").map(|(_, expected)| expected.to_string())),
+            _ => None,
+        };
+
+        SchemaError { schema: name.to_string(), path: pointer_path, kind, expected, rule: None, message }
+    }
+
+    /// Error of the missing `field` (its JSON name) of the schema `name` described by `schema`.
+    pub fn missing(name: &str, schema: &Value, field: &str) -> SchemaError {
+        let path = pointer("", field);
+
+        SchemaError {
+            schema: name.to_string(),
+            expected: expected_type(schema, &path),
+            path,
+            kind: ErrorKind::MissingField,
+            rule: None,
+            message: format!("missing field `{}`", field),
+        }
+    }
+
+    /// Validation error of the schema `name`.
+    pub fn violation(name: &str, violation: &Violation) -> SchemaError {
+        SchemaError {
+            schema: name.to_string(),
+            path: violation.path.clone(),
+            kind: ErrorKind::Validation,
+            expected: None,
+            rule: Some(violation.rule.clone()),
+            message: violation.message.clone(),
+        }
+    }
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}: {}", self.schema, if self.path.is_empty() { "/" } else { &self.path }, self.message)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// JSON type of the values at `path` in the documents of `schema`, following objects and arrays.
+fn expected_type(schema: &Value, path: &str) -> Option<String> {
+    let mut schema = schema;
+    for token in path.split('/').skip(1) {
+        let token = token.replace("~1", "/").replace("~0", "~");
+
+        schema = match (schema.get("properties").and_then(|properties| properties.get(&token)), schema.get("items")) {
+            (Some(property), _) => property,
+            (None, Some(items)) if token.parse::<usize>().is_ok() => items,
+            _ => schema.get("additionalProperties").filter(|value| value.is_object())?,
+        };
+    }
+
+    match schema.get("type")? {
+        Value::String(kind) => Some(kind.clone()),
+        Value::Array(kinds) => Some(kinds.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" or ")),
+        _ => None,
+    }
+}
+
+/// Error of [`Schema::from_valid_json`](super::Schema::from_valid_json): the JSON couldn't be decoded,
+/// or it was decoded but breaks some validation rules.
+#[derive(Debug)]
+pub enum DecodeError {
+    Json(SchemaError),
+    Invalid(Vec<SchemaError>),
+}
+
+impl DecodeError {
+    /// Every error, whichever the variant, e.g. for the body of a 422 response.
+    pub fn errors(&self) -> &[SchemaError] {
+        match self {
+            DecodeError::Json(error) => std::slice::from_ref(error),
+            DecodeError::Invalid(errors) => errors,
+        }
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let errors: Vec<String> = self.errors().iter().map(ToString::to_string).collect();
+        write!(f, "{}", errors.join("; "))
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<SchemaError> for DecodeError {
+    fn from(error: SchemaError) -> Self {
+        DecodeError::Json(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_expected_type() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "scores": { "type": "object", "additionalProperties": { "type": ["number", "null"] } },
+            },
+        });
+
+        assert_eq!(expected_type(&schema, ""), Some(String::from("object")));
+        assert_eq!(expected_type(&schema, "/tags/3"), Some(String::from("string")));
+        assert_eq!(expected_type(&schema, "/scores/a~1b"), Some(String::from("number or null")));
+        assert_eq!(expected_type(&schema, "/other"), None);
+    }
+
+    #[test]
+    fn test_violation() {
+        let error = SchemaError::violation("Profile", &Violation::new("/age", "range", "must be in 0..=130"));
+
+        assert_eq!(error.to_string(), "Profile at /age: must be in 0..=130");
+        assert_eq!(serde_json::to_value(&error).unwrap(), json!({
+            "schema": "Profile",
+            "path": "/age",
+            "kind": "validation",
+            "rule": "range",
+            "message": "must be in 0..=130",
+        }));
+    }
+}
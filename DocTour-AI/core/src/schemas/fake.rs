@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use super::Schema;
+
+const FIRST_NAMES: &[&str] = &["ana", "ben", "chloe", "david", "emma", "farid", "grace", "hugo", "ines", "jun"];
+const LAST_NAMES: &[&str] = &["martin", "smith", "garcia", "muller", "rossi", "tanaka", "silva", "novak"];
+const DOMAINS: &[&str] = &["example.com", "example.org", "doctour.ai", "mail.test"];
+const WORDS: &[&str] = &["guide", "tour", "docs", "search", "rust", "python", "index", "chapter", "notes", "api"];
+
+/// Depth from which arrays, maps and values of any type are kept small, and optional values left out, so
+/// recursive schemas end.
+const MAX_DEPTH: usize = 4;
+
+fn pick<'a, R: Rng + ?Sized>(values: &[&'a str], rng: &mut R) -> &'a str {
+    values.choose(rng).copied().unwrap_or_default()
+}
+
+/// An integer bound of `schema`, as an `i128` so every `u64` and `i64` fits.
+fn integer(schema: &Value, keyword: &str) -> Option<i128> {
+    let bound = schema.get(keyword)?;
+    bound.as_i64().map(i128::from).or_else(|| bound.as_u64().map(i128::from)).or_else(|| bound.as_f64().map(|bound| bound as i128))
+}
+
+/// A length bound of `schema`.
+fn length(schema: &Value, keyword: &str) -> Option<usize> {
+    schema.get(keyword).and_then(Value::as_u64).map(|length| length as usize)
+}
+
+/// A length between the `minimum` and `maximum` keywords of `schema`, at most a few more than the minimum.
+fn count<R: Rng + ?Sized>(schema: &Value, (minimum, maximum): (&str, &str), spread: usize, rng: &mut R) -> usize {
+    let minimum = length(schema, minimum).unwrap_or(0);
+    let maximum = length(schema, maximum).unwrap_or(minimum + spread).min(minimum + spread).max(minimum);
+
+    rng.gen_range(minimum..=maximum)
+}
+
+/// A realistic string of the `format` of `schema` (`email`, `uri` and `date`), else words, or letters and digits
+/// when its length is bounded.
+fn string<R: Rng + ?Sized>(schema: &Value, rng: &mut R) -> String {
+    match schema.get("format").and_then(Value::as_str) {
+        Some("email") => format!("{}.{}@{}", pick(FIRST_NAMES, rng), pick(LAST_NAMES, rng), pick(DOMAINS, rng)),
+        Some("uri") => format!("https://{}/{}/{}", pick(DOMAINS, rng), pick(WORDS, rng), rng.gen_range(1..1000)),
+        Some("date") => format!("{:04}-{:02}-{:02}", rng.gen_range(1970..=2035), rng.gen_range(1..=12), rng.gen_range(1..=28)),
+        _ if schema.get("minLength").is_some() || schema.get("maxLength").is_some() => {
+            let length = count(schema, ("minLength", "maxLength"), 16, rng);
+            (0..length).map(|_| rng.sample(rand::distributions::Alphanumeric) as char).collect()
+        }
+        _ => {
+            let words: Vec<&str> = (0..rng.gen_range(1..=3)).map(|_| pick(WORDS, rng)).collect();
+            words.join(" ")
+        }
+    }
+}
+
+/// A number between the bounds of `schema`, around 0 without any.
+fn number<R: Rng + ?Sized>(schema: &Value, rng: &mut R) -> Value {
+    let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+    let minimum = bound("minimum").unwrap_or(-1000.0);
+
+    let value = match (bound("maximum"), bound("exclusiveMaximum")) {
+        (_, Some(maximum)) if maximum > minimum => rng.gen_range(minimum..maximum),
+        (Some(maximum), _) if maximum >= minimum => rng.gen_range(minimum..=maximum),
+        _ => rng.gen_range(minimum..=minimum + 2000.0),
+    };
+    Value::from(value)
+}
+
+/// An integer between the bounds of `schema`, around 0 without any.
+fn integer_value<R: Rng + ?Sized>(schema: &Value, rng: &mut R) -> Value {
+    let minimum = integer(schema, "minimum").unwrap_or(-1000);
+    let maximum = integer(schema, "exclusiveMaximum").map(|maximum| maximum - 1)
+        .or_else(|| integer(schema, "maximum"))
+        .filter(|maximum| *maximum >= minimum)
+        .unwrap_or(minimum.saturating_add(2000));
+
+    // Small values are more realistic, unless the bounds are far from 0
+    let (low, high) = (minimum.max(-1000), maximum.min(1000));
+    let value = if low <= high && rng.gen_bool(0.9) { rng.gen_range(low..=high) } else { rng.gen_range(minimum..=maximum) };
+
+    i64::try_from(value).map(Value::from).unwrap_or_else(|_| Value::from(value as u64))
+}
+
+/// Subschemas of `schema` with an `$anchor`, which `$ref`s refer to, by name.
+fn anchors<'a>(schema: &'a Value, anchors: &mut BTreeMap<&'a str, &'a Value>) {
+    match schema {
+        Value::Object(object) => {
+            if let Some(anchor) = object.get("$anchor").and_then(Value::as_str) {
+                anchors.insert(anchor, schema);
+            }
+            object.values().for_each(|value| self::anchors(value, anchors));
+        }
+        Value::Array(values) => values.iter().for_each(|value| self::anchors(value, anchors)),
+        _ => {}
+    }
+}
+
+/// A random JSON document of `schema`: its types, bounds, lengths, formats, constants and enums, with its
+/// required properties and some of its optional ones, but none of its read-only ones.
+pub fn value<R: Rng + ?Sized>(schema: &Value, rng: &mut R) -> Value {
+    let mut anchored = BTreeMap::new();
+    anchors(schema, &mut anchored);
+
+    generate(schema, &anchored, rng, 0)
+}
+
+fn generate<R: Rng + ?Sized>(schema: &Value, anchors: &BTreeMap<&str, &Value>, rng: &mut R, depth: usize) -> Value {
+    let deep = depth >= MAX_DEPTH;
+    let null = |schema: &&Value| schema.get("type").is_some_and(|kind| kind == "null");
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        return match anchors.get(reference.trim_start_matches('#')) {
+            Some(schema) => generate(schema, anchors, rng, depth),
+            None => Value::Null,
+        };
+    }
+    if let Some(value) = schema.get("const") {
+        return value.clone();
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return values.choose(rng).cloned().unwrap_or(Value::Null);
+    }
+    if let Some(variants) = schema.get("oneOf").or_else(|| schema.get("anyOf")).and_then(Value::as_array) {
+        let variant = match variants.iter().find(null) {
+            Some(variant) if deep => Some(variant),
+            _ => variants.choose(rng),
+        };
+        return variant.map(|variant| generate(variant, anchors, rng, depth)).unwrap_or(Value::Null);
+    }
+
+    let kind = match schema.get("type") {
+        Some(Value::Array(kinds)) if deep && kinds.iter().any(|kind| kind == "null") => "null",
+        Some(Value::Array(kinds)) => kinds.choose(rng).and_then(Value::as_str).unwrap_or("null"),
+        Some(kind) => kind.as_str().unwrap_or("null"),
+        // Any value: a scalar
+        None => ["null", "boolean", "integer", "string"].choose(rng).copied().unwrap_or("null"),
+    };
+    let spread = if deep { 0 } else { 4 };
+
+    match kind {
+        "boolean" => Value::from(rng.gen::<bool>()),
+        "integer" => integer_value(schema, rng),
+        "number" => number(schema, rng),
+        "string" => Value::from(string(schema, rng)),
+        "array" => match schema.get("items") {
+            Some(items) => {
+                let length = count(schema, ("minItems", "maxItems"), spread, rng);
+                Value::Array((0..length).map(|_| generate(items, anchors, rng, depth + 1)).collect())
+            }
+            None => Value::Array(Vec::new()),
+        },
+        "object" => {
+            let mut object = Map::new();
+            let required = schema.get("required").and_then(Value::as_array);
+
+            for (name, property) in schema.get("properties").and_then(Value::as_object).into_iter().flatten() {
+                if property.get("readOnly").is_some_and(|read_only| read_only == true) {
+                    continue;
+                }
+                let required = required.is_some_and(|required| required.iter().any(|other| other == name));
+                // Constants, e.g. versions and tags, are always given
+                if required || property.get("const").is_some() || (!deep && rng.gen_bool(0.5)) {
+                    object.insert(name.clone(), generate(property, anchors, rng, depth + 1));
+                }
+            }
+            if let Some(values) = schema.get("additionalProperties").filter(|values| values.is_object()) {
+                for _ in 0..count(schema, ("minProperties", "maxProperties"), spread, rng) {
+                    let key = format!("{}_{}", pick(WORDS, rng), rng.gen_range(0..100));
+                    object.insert(key, generate(values, anchors, rng, depth + 1));
+                }
+            }
+
+            Value::Object(object)
+        }
+        _ => Value::Null,
+    }
+}
+
+/// A random value of `T`, from a [`value`] of its JSON Schema. It breaks the validation rules whose ranges aren't
+/// literals, which aren't in the JSON Schema.
+///
+/// # Panics
+///
+/// When the document doesn't decode as a `T`, because its JSON Schema doesn't describe it.
+pub fn fake<T: Schema + DeserializeOwned, R: Rng + ?Sized>(rng: &mut R) -> T {
+    let document = value(&T::subschema(), rng);
+
+    T::from_json(&document.to_string()).unwrap_or_else(|error| panic!("Fake {} doesn't decode: {} ({})", T::schema_name(), error, document))
+}
+
+/// Strategy of `proptest` generating values of `T` with [`fake`], from a seed. Failures shrink the seed, not the
+/// value.
+#[cfg(feature = "proptest")]
+pub fn strategy<T>() -> impl proptest::strategy::Strategy<Value = T>
+    where
+        T: Schema + DeserializeOwned + std::fmt::Debug,
+{
+    use proptest::strategy::Strategy;
+    use rand::SeedableRng;
+
+    proptest::prelude::any::<u64>().prop_map(|seed| fake::<T, _>(&mut rand::rngs::StdRng::seed_from_u64(seed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::schemas::validation::rules;
+    use crate::schemas::{Registry, Validate};
+    use super::*;
+
+    crate::schema!(
+        Contact {
+            email: String where email,
+            website: Option<String> where url,
+            birthday: String where date,
+            age: u8 where range(18..=99),
+            score: f64 where range(0.0..1.0),
+            tags: Vec<String> where len(1..=3) each(len(2..=8)),
+        }
+    );
+
+    crate::schema!(
+        Outline {
+            title: String,
+            children: Vec<Outline>,
+            next: Option<Box<Outline>>,
+        }
+    );
+
+    #[test]
+    fn test_fake() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _ in 0..100 {
+            let contact: Contact = fake(&mut rng);
+
+            assert!(rules::email(&contact.email) && rules::date(&contact.birthday), "{:?}", contact);
+            assert!(contact.website.as_deref().is_none_or(rules::url), "{:?}", contact);
+            assert!((18..=99).contains(&contact.age) && (0.0..1.0).contains(&contact.score), "{:?}", contact);
+            assert_eq!(contact.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_recursive_fake() {
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let outlines: Vec<Outline> = (0..50).map(|_| fake(&mut rng)).collect();
+        assert!(outlines.iter().any(|outline| !outline.children.is_empty() || outline.next.is_some()));
+    }
+
+    /// Every registered schema: fake values validate, and go through JSON unchanged.
+    #[test]
+    fn test_registry_invariants() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for entry in Registry::open().schemas() {
+            for _ in 0..20 {
+                let value = entry.fake(&mut rng);
+                assert_eq!(value.dyn_validate(), Ok(()), "{}", entry.name);
+
+                let json = value.dyn_to_json().unwrap();
+                let decoded = entry.decode(&json).unwrap_or_else(|error| panic!("{}: {} ({})", entry.name, error, json));
+                assert_eq!(decoded.dyn_to_value().unwrap(), value.dyn_to_value().unwrap(), "{}", entry.name);
+            }
+        }
+    }
+
+    #[cfg(feature = "proptest")]
+    proptest::proptest! {
+        #[test]
+        fn test_strategy(contact in strategy::<Contact>()) {
+            proptest::prop_assert_eq!(Contact::from_json(&contact.to_json().unwrap()).unwrap(), contact.clone());
+            proptest::prop_assert_eq!(contact.validate(), Ok(()));
+        }
+    }
+}
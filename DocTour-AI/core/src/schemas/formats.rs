@@ -0,0 +1,189 @@
+use std::fmt::{Debug, Display, Formatter};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{Schema, SchemaError};
+
+/// Error of every wire format of [`Schema`], besides the JSON of `to_json` and `from_json`.
+/// Each format only exists with its cargo feature: `msgpack`, `cbor` and `yaml`.
+#[derive(Debug)]
+pub enum FormatError {
+    Json(serde_json::Error),
+    #[cfg(feature = "msgpack")]
+    MsgPackEncode(rmp_serde::encode::Error),
+    #[cfg(feature = "msgpack")]
+    MsgPackDecode(rmp_serde::decode::Error),
+    #[cfg(feature = "cbor")]
+    CborEncode(ciborium::ser::Error<std::io::Error>),
+    #[cfg(feature = "cbor")]
+    CborDecode(ciborium::de::Error<std::io::Error>),
+    #[cfg(feature = "yaml")]
+    Yaml(serde_yaml::Error),
+    /// A document of a versioned schema, read from the format, that doesn't upgrade or decode as the schema.
+    Schema(&'static str, Box<SchemaError>),
+}
+
+impl FormatError {
+    /// Name of the format that failed.
+    pub fn format(&self) -> &'static str {
+        match self {
+            FormatError::Json(_) => "json",
+            #[cfg(feature = "msgpack")]
+            FormatError::MsgPackEncode(_) | FormatError::MsgPackDecode(_) => "msgpack",
+            #[cfg(feature = "cbor")]
+            FormatError::CborEncode(_) | FormatError::CborDecode(_) => "cbor",
+            #[cfg(feature = "yaml")]
+            FormatError::Yaml(_) => "yaml",
+            FormatError::Schema(format, _) => format,
+        }
+    }
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let format = self.format();
+
+        match self {
+            FormatError::Json(error) => write!(f, "{}: {}", format, error),
+            #[cfg(feature = "msgpack")]
+            FormatError::MsgPackEncode(error) => write!(f, "{}: {}", format, error),
+            #[cfg(feature = "msgpack")]
+            FormatError::MsgPackDecode(error) => write!(f, "{}: {}", format, error),
+            #[cfg(feature = "cbor")]
+            FormatError::CborEncode(error) => write!(f, "{}: {}", format, error),
+            #[cfg(feature = "cbor")]
+            FormatError::CborDecode(error) => write!(f, "{}: {}", format, error),
+            #[cfg(feature = "yaml")]
+            FormatError::Yaml(error) => write!(f, "{}: {}", format, error),
+            FormatError::Schema(_, error) => write!(f, "{}: {}", format, error),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<serde_json::Error> for FormatError {
+    fn from(error: serde_json::Error) -> Self {
+        FormatError::Json(error)
+    }
+}
+
+/// Serializes `value` to JSON and every enabled format, then back, and panics unless it comes back equal.
+pub fn assert_round_trip<T>(value: &T)
+    where
+        T: Schema + Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let json = value.to_json().expect("Serializing to JSON");
+    assert_eq!(&T::from_json(&json).expect("Deserializing from JSON"), value, "JSON round trip");
+
+    #[cfg(feature = "msgpack")]
+    {
+        let bytes = value.to_msgpack().expect("Serializing to MessagePack");
+        assert_eq!(&T::from_msgpack(&bytes).expect("Deserializing from MessagePack"), value, "MessagePack round trip");
+    }
+
+    #[cfg(feature = "cbor")]
+    {
+        let bytes = value.to_cbor().expect("Serializing to CBOR");
+        assert_eq!(&T::from_cbor(&bytes).expect("Deserializing from CBOR"), value, "CBOR round trip");
+    }
+
+    #[cfg(feature = "yaml")]
+    {
+        let yaml = value.to_yaml().expect("Serializing to YAML");
+        assert_eq!(&T::from_yaml(&yaml).expect("Deserializing from YAML"), value, "YAML round trip");
+    }
+}
+
+/// Round trip of random values of `T`, from the seeds `0..count`, so a failure happens again on the next run.
+#[cfg(feature = "fake")]
+pub fn assert_fake_round_trips<T>(count: u64)
+    where
+        T: Schema + Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    use rand::SeedableRng;
+
+    for seed in 0..count {
+        assert_round_trip(&super::fake::fake::<T, _>(&mut rand::rngs::StdRng::seed_from_u64(seed)));
+    }
+}
+
+/// Sample of the round-trip test of a type without the `fake` feature: its default value.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` has no sample for its round-trip test",
+    note = "give it a `Default`, a `#[schema(sample = <expr>)]`, or `#[schema(round_trip = false)]`, or turn on the `fake` feature of `doctour_ai`"
+)]
+pub trait DefaultSample: Sized {
+    fn sample() -> Self;
+}
+
+impl<T: Default> DefaultSample for T {
+    fn sample() -> Self {
+        T::default()
+    }
+}
+
+/// Test of the round trip of `$name` through JSON and every enabled wire format, which the `Schema` derive
+/// generates next to each type: of `$sample` when given, and of random values with the `fake` feature.
+#[doc(hidden)]
+#[cfg(feature = "fake")]
+#[macro_export]
+macro_rules! __round_trip_test {
+    ($test:ident, $name:ty $(, $sample:expr)?) => {
+        #[cfg(test)]
+        #[test]
+        #[allow(non_snake_case)]
+        fn $test() {
+            $($crate::schemas::formats::assert_round_trip::<$name>(&$sample);)?
+            $crate::schemas::formats::assert_fake_round_trips::<$name>(16);
+        }
+    };
+}
+
+/// Test of the round trip of `$name` through JSON and every enabled wire format, which the `Schema` derive
+/// generates next to each type: of `$sample` when given, or else of its default value.
+#[doc(hidden)]
+#[cfg(not(feature = "fake"))]
+#[macro_export]
+macro_rules! __round_trip_test {
+    ($test:ident, $name:ty, $sample:expr) => {
+        #[cfg(test)]
+        #[test]
+        #[allow(non_snake_case)]
+        fn $test() {
+            $crate::schemas::formats::assert_round_trip::<$name>(&$sample);
+        }
+    };
+    ($test:ident, $name:ty) => {
+        #[cfg(test)]
+        #[test]
+        #[allow(non_snake_case)]
+        fn $test() {
+            $crate::schemas::formats::assert_round_trip(&<$name as $crate::schemas::formats::DefaultSample>::sample());
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error() {
+        let error = FormatError::from(serde_json::from_str::<u8>("x").unwrap_err());
+
+        assert_eq!(error.format(), "json");
+        assert!(error.to_string().starts_with("json: expected value"));
+    }
+
+    crate::schema!(Point { x: i32 });
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_decode_error() {
+        use crate::schemas::Schema;
+
+        assert!(matches!(Point::from_msgpack(&[0xc1]), Err(FormatError::MsgPackDecode(_))));
+    }
+}
@@ -0,0 +1,260 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde_json::{json, Value};
+
+/// Dialect of every generated schema.
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// `JsonSchema` is a trait that describes the JSON values of a type with a JSON Schema (Draft 2020-12).
+///
+/// It is implemented for the primitive and collection types serde supports, and for every type deriving
+/// `schema_macro::Schema`, so `schema!` structs can be nested in each other.
+pub trait JsonSchema {
+    /// Schema of the type's values, to be embedded in the schema of another type.
+    fn subschema() -> Value;
+
+    /// Standalone schema of the type, with its `$schema` dialect. A recursive type found more than once in it
+    /// is only defined the first time, and referred to the others.
+    fn json_schema() -> Value {
+        let mut schema = Self::subschema();
+        deduplicate(&mut schema, &mut BTreeSet::new());
+        if let Value::Object(object) = &mut schema {
+            object.insert(String::from("$schema"), Value::from(DIALECT));
+        }
+
+        schema
+    }
+}
+
+thread_local! {
+    /// Types whose schema is being built by [`recursive`], and whether they were found in it.
+    static BUILDING: RefCell<Vec<(&'static str, bool)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Pops the type [`recursive`] pushed, even when building its schema panics.
+struct Built;
+
+impl Drop for Built {
+    fn drop(&mut self) {
+        BUILDING.with(|building| building.borrow_mut().pop());
+    }
+}
+
+/// `$anchor` of the type `type_name`: its path, e.g. `doctour_ai.schemas.Section`, as anchors can't have `::`,
+/// so types of the same name in other modules have their own.
+pub fn anchor(type_name: &str) -> String {
+    type_name.replace("::", ".").chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_') { c } else { '_' })
+        .collect()
+}
+
+/// Schema of the type `type_name`, from `build`, for types that may contain themselves (`Vec<Self>`,
+/// `Option<Box<Self>>`...). Within `build`, the type is a `{ "$ref": "#<anchor>" }`, to the [`anchor`] the
+/// schema then gets, instead of being inlined forever. Used by `schema_macro::Schema`.
+pub fn recursive(type_name: &'static str, build: impl FnOnce() -> Value) -> Value {
+    let nested = BUILDING.with(|building| {
+        let mut building = building.borrow_mut();
+        match building.iter_mut().find(|(building, _)| *building == type_name) {
+            Some((_, found)) => {
+                *found = true;
+                true
+            }
+            None => {
+                building.push((type_name, false));
+                false
+            }
+        }
+    });
+    if nested {
+        return json!({ "$ref": format!("#{}", anchor(type_name)) });
+    }
+
+    let built = Built;
+    let mut schema = build();
+    let found = BUILDING.with(|building| building.borrow().last().is_some_and(|(_, found)| *found));
+    drop(built);
+
+    if let (true, Value::Object(object)) = (found, &mut schema) {
+        object.insert(String::from("$anchor"), Value::from(anchor(type_name)));
+    }
+
+    schema
+}
+
+/// Turns the subschemas of `schema` whose `$anchor` is already `defined` into `$ref`s to it, as an anchor
+/// must be unique in its document.
+fn deduplicate(schema: &mut Value, defined: &mut BTreeSet<String>) {
+    match schema {
+        Value::Object(object) => {
+            if let Some(anchor) = object.get("$anchor").and_then(Value::as_str).map(str::to_string) {
+                if !defined.insert(anchor.clone()) {
+                    *schema = json!({ "$ref": format!("#{}", anchor) });
+                    return;
+                }
+            }
+            object.values_mut().for_each(|value| deduplicate(value, defined));
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| deduplicate(value, defined)),
+        _ => {}
+    }
+}
+
+/// Implements `JsonSchema` for types sharing the same schema.
+macro_rules! json_schema {
+    ($($type:ty),+ => $schema:expr) => {
+        $(
+            impl JsonSchema for $type {
+                fn subschema() -> Value {
+                    $schema
+                }
+            }
+        )+
+    };
+}
+
+/// Implements `JsonSchema` for integers, bounded by the type's range.
+macro_rules! integer {
+    ($($type:ty),+) => {
+        $(
+            impl JsonSchema for $type {
+                fn subschema() -> Value {
+                    json!({ "type": "integer", "minimum": <$type>::MIN, "maximum": <$type>::MAX })
+                }
+            }
+        )+
+    };
+}
+
+json_schema!(bool => json!({ "type": "boolean" }));
+json_schema!(String, str => json!({ "type": "string" }));
+json_schema!(char => json!({ "type": "string", "minLength": 1, "maxLength": 1 }));
+json_schema!(f32, f64 => json!({ "type": "number" }));
+json_schema!(() => json!({ "type": "null" }));
+json_schema!(Value => json!({}));
+integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl<T: JsonSchema + ?Sized> JsonSchema for &T {
+    fn subschema() -> Value {
+        T::subschema()
+    }
+}
+
+impl<T: JsonSchema + ?Sized> JsonSchema for Box<T> {
+    fn subschema() -> Value {
+        T::subschema()
+    }
+}
+
+/// A missing or `null` value. Fields of this type aren't required in their object.
+impl<T: JsonSchema> JsonSchema for Option<T> {
+    fn subschema() -> Value {
+        let mut schema = T::subschema();
+
+        // The `$ref`s to an anchored schema, and the values of an `enum` or a `const`, don't accept `null`
+        let closed = ["$anchor", "enum", "const"].iter().any(|keyword| schema.get(keyword).is_some());
+        match schema.get("type").cloned() {
+            Some(Value::String(kind)) if !closed => {
+                schema["type"] = json!([kind, "null"]);
+                schema
+            }
+            _ => json!({ "anyOf": [schema, { "type": "null" }] }),
+        }
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Vec<T> {
+    fn subschema() -> Value {
+        json!({ "type": "array", "items": T::subschema() })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for [T] {
+    fn subschema() -> Value {
+        json!({ "type": "array", "items": T::subschema() })
+    }
+}
+
+impl<T: JsonSchema, const N: usize> JsonSchema for [T; N] {
+    fn subschema() -> Value {
+        json!({ "type": "array", "items": T::subschema(), "minItems": N, "maxItems": N })
+    }
+}
+
+impl<T: JsonSchema, S> JsonSchema for HashSet<T, S> {
+    fn subschema() -> Value {
+        json!({ "type": "array", "items": T::subschema(), "uniqueItems": true })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for BTreeSet<T> {
+    fn subschema() -> Value {
+        json!({ "type": "array", "items": T::subschema(), "uniqueItems": true })
+    }
+}
+
+impl<T: JsonSchema, S> JsonSchema for HashMap<String, T, S> {
+    fn subschema() -> Value {
+        json!({ "type": "object", "additionalProperties": T::subschema() })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for BTreeMap<String, T> {
+    fn subschema() -> Value {
+        json!({ "type": "object", "additionalProperties": T::subschema() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primitives() {
+        assert_eq!(bool::subschema(), json!({ "type": "boolean" }));
+        assert_eq!(u8::subschema(), json!({ "type": "integer", "minimum": 0, "maximum": 255 }));
+        assert_eq!(f32::subschema(), json!({ "type": "number" }));
+        assert_eq!(String::json_schema(), json!({ "$schema": DIALECT, "type": "string" }));
+    }
+
+    #[test]
+    fn test_collections() {
+        assert_eq!(Option::<String>::subschema(), json!({ "type": ["string", "null"] }));
+        assert_eq!(Option::<Value>::subschema(), json!({ "anyOf": [{}, { "type": "null" }] }));
+        assert_eq!(Vec::<bool>::subschema(), json!({ "type": "array", "items": { "type": "boolean" } }));
+        assert_eq!(HashMap::<String, f64>::subschema(),
+                   json!({ "type": "object", "additionalProperties": { "type": "number" } }));
+    }
+
+    #[derive(Debug, PartialEq, crate::schemas::Schema, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    #[schema(sample = Severity::High)]
+    enum Severity {
+        Low,
+        High,
+    }
+
+    /// Whether `value` matches `schema`, for the keywords `Option` deals with.
+    fn accepts(schema: &Value, value: &Value) -> bool {
+        let kind = |kind: &Value| match kind.as_str() {
+            Some("null") => value.is_null(),
+            Some("string") => value.is_string(),
+            _ => false,
+        };
+
+        schema.get("anyOf").and_then(Value::as_array).is_none_or(|schemas| schemas.iter().any(|schema| accepts(schema, value)))
+            && schema.get("type").is_none_or(|kinds| kinds.as_array().map_or_else(|| kind(kinds), |kinds| kinds.iter().any(kind)))
+            && schema.get("enum").and_then(Value::as_array).is_none_or(|values| values.contains(value))
+            && schema.get("const").is_none_or(|constant| constant == value)
+    }
+
+    #[test]
+    fn test_optional_enum() {
+        let schema = Option::<Severity>::subschema();
+
+        assert!(accepts(&schema, &Value::Null));
+        assert!(accepts(&schema, &json!("low")));
+        assert!(!accepts(&schema, &json!("medium")));
+        assert!(!accepts(&Severity::subschema(), &Value::Null));
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt::Debug;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{ErrorKind, Schema, SchemaError};
+
+/// Key of the version in the JSON documents of versioned schemas.
+pub const VERSION_KEY: &str = "$v";
+
+/// Upgrades a document of a schema by one version, e.g. from 1 to 2, or tells why it can't.
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+pub(super) fn error<T: Schema>(message: String) -> SchemaError {
+    SchemaError {
+        schema: T::schema_name(),
+        path: String::new(),
+        kind: ErrorKind::Migration,
+        expected: None,
+        rule: None,
+        message,
+    }
+}
+
+/// Adds the `version` of its schema to `document`, as its first member. Only objects carry their version.
+pub fn stamp(document: &mut Value, version: u32) {
+    if let Some(object) = document.as_object_mut() {
+        object.remove(VERSION_KEY);
+
+        let mut stamped = Map::from_iter([(String::from(VERSION_KEY), Value::from(version))]);
+        stamped.append(object);
+        *object = stamped;
+    }
+}
+
+/// `document`, a document of `T`, stamped with the current version if `T` is versioned.
+pub fn stamp_current<T: Schema + ?Sized>(mut document: Value) -> Value {
+    if T::VERSIONED {
+        stamp(&mut document, T::VERSION);
+    }
+    document
+}
+
+/// Removes the version of `document`, 1 when it has none (it predates versions), and runs the migrations
+/// of `T` from it to `T::VERSION`.
+#[allow(clippy::result_large_err)]
+pub fn upgrade<T: Schema>(mut document: Value) -> Result<Value, SchemaError> {
+    let version = match document.as_object_mut().and_then(|object| object.remove(VERSION_KEY)) {
+        None => 1,
+        Some(version) => version.as_u64().and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| error::<T>(format!("invalid version {}", version)))?,
+    };
+    if version > T::VERSION {
+        return Err(error::<T>(format!("version {} is newer than {}", version, T::VERSION)));
+    }
+
+    for from in version..T::VERSION {
+        let migration = T::MIGRATIONS.get(from as usize - 1)
+            .ok_or_else(|| error::<T>(format!("no migration from version {}", from)))?;
+        document = migration(document).map_err(|message| error::<T>(format!("migrating from version {}: {}", from, message)))?;
+    }
+
+    Ok(document)
+}
+
+/// Parses `json`, a document of `T`, without decoding it.
+#[allow(clippy::result_large_err)]
+pub fn parse<T: Schema + ?Sized>(json: &str) -> Result<Value, SchemaError> {
+    serde_json::from_str(json).map_err(|error| SchemaError::json(&T::schema_name(), &T::subschema(), &serde_path_to_error::Track::new().path(), &error))
+}
+
+/// Decodes `document` as a `T`, once upgraded to the current version. Used by the decoding methods of versioned
+/// schemas, whatever the format `document` was read from. The upgraded document is owned, so `T` can't borrow.
+#[allow(clippy::result_large_err)]
+pub fn from_document<T: Schema + DeserializeOwned>(document: Value) -> Result<T, SchemaError> {
+    let document = upgrade::<T>(document)?;
+
+    let mut track = serde_path_to_error::Track::new();
+    T::deserialize(serde_path_to_error::Deserializer::new(document, &mut track))
+        .map_err(|error| SchemaError::json(&T::schema_name(), &T::subschema(), &track.path(), &error))
+}
+
+/// Decodes every `.json` fixture of `dir` (stored documents of any version) as a `T`, through every migration
+/// from its version, and panics with the fixture's name unless it decodes, validates, and re-encodes as the
+/// same value of the current version. Returns the number of fixtures.
+pub fn assert_fixtures<T>(dir: impl AsRef<Path>) -> usize
+    where
+        T: Schema + Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let dir = dir.as_ref();
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap_or_else(|error| panic!("Reading the fixtures of {}: {}", dir.display(), error))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "No fixtures in {}", dir.display());
+
+    for path in &paths {
+        let json = std::fs::read_to_string(path).unwrap_or_else(|error| panic!("Reading {}: {}", path.display(), error));
+        let value = T::from_valid_json(&json).unwrap_or_else(|error| panic!("Decoding {}: {}", path.display(), error));
+
+        let current = value.to_json().unwrap_or_else(|error| panic!("Encoding {}: {}", path.display(), error));
+        assert_eq!(T::from_json(&current).ok().as_ref(), Some(&value), "Round trip of {}", path.display());
+    }
+
+    paths.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::schemas::JsonSchema;
+    use super::*;
+
+    /// Renames `text` to `question`.
+    fn v1_to_v2(mut document: Value) -> Result<Value, String> {
+        let text = document.as_object_mut().and_then(|object| object.remove("text")).ok_or("missing text")?;
+        document["question"] = text;
+
+        Ok(document)
+    }
+
+    /// Adds `language`, English until then.
+    fn v2_to_v3(mut document: Value) -> Result<Value, String> {
+        document["language"] = Value::from("en");
+
+        Ok(document)
+    }
+
+    crate::schema!(
+        #[strict]
+        #[version = 3]
+        #[migrations(v1_to_v2, v2_to_v3)]
+        StoredQuestion {
+            question: String where len(1..),
+            top_k: u32 = 5,
+            language: String,
+        }
+    );
+
+    #[test]
+    fn test_stamp() {
+        let (mut object, mut empty, mut list) = (json!({ "a": 1 }), json!({}), json!([]));
+        stamp(&mut object, 2);
+        stamp(&mut empty, 2);
+        stamp(&mut list, 2);
+
+        assert_eq!(object.to_string(), r#"{"$v":2,"a":1}"#);
+        assert_eq!(empty, json!({ "$v": 2 }));
+        assert_eq!(list, json!([]));
+    }
+
+    #[test]
+    fn test_versioned_json() {
+        let question = StoredQuestion { question: String::from("?"), top_k: 5, language: String::from("en") };
+
+        assert_eq!(question.to_json().unwrap(), r#"{"$v":3,"question":"?","top_k":5,"language":"en"}"#);
+        assert_eq!(StoredQuestion::from_json(r#"{"text":"?"}"#).unwrap(), question);
+        assert_eq!(StoredQuestion::from_json(r#"{"$v":2,"question":"?"}"#).unwrap(), question);
+        assert_eq!(StoredQuestion::json_schema()["properties"]["$v"], json!({ "const": 3 }));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_versioned_yaml() {
+        let question = StoredQuestion { question: String::from("?"), top_k: 5, language: String::from("en") };
+
+        assert!(question.to_yaml().unwrap().starts_with("$v: 3\n"));
+        assert_eq!(StoredQuestion::from_yaml(&question.to_yaml().unwrap()).unwrap(), question);
+        assert_eq!(StoredQuestion::from_yaml("text: '?'\n").unwrap(), question);
+        assert_eq!(StoredQuestion::from_yaml("$v: 4\n").unwrap_err().format(), "yaml");
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_versioned_msgpack() {
+        let question = StoredQuestion { question: String::from("?"), top_k: 5, language: String::from("en") };
+        let bytes = question.to_msgpack().unwrap();
+
+        assert_eq!(rmp_serde::from_slice::<Value>(&bytes).unwrap()["$v"], 3);
+        assert_eq!(StoredQuestion::from_msgpack(&bytes).unwrap(), question);
+        assert_eq!(StoredQuestion::from_msgpack(&rmp_serde::to_vec_named(&json!({ "text": "?" })).unwrap()).unwrap(), question);
+    }
+
+    #[test]
+    fn test_migration_errors() {
+        let error = StoredQuestion::from_json(r#"{"$v":4,"question":"?"}"#).unwrap_err();
+        assert_eq!((error.kind, error.message.as_str()), (ErrorKind::Migration, "version 4 is newer than 3"));
+
+        let error = StoredQuestion::from_json(r#"{"question":"?"}"#).unwrap_err();
+        assert_eq!(error.message, "migrating from version 1: missing text");
+
+        let error = StoredQuestion::from_json(r#"{"$v":3,"question":"?","language":1}"#).unwrap_err();
+        assert_eq!((error.path.as_str(), error.kind), ("/language", ErrorKind::InvalidType));
+    }
+
+    #[test]
+    fn test_fixtures() {
+        assert_eq!(assert_fixtures::<StoredQuestion>(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/stored_question")), 3);
+    }
+}
@@ -1,20 +1,67 @@
+pub use schema_macro::{defaults, patch, Schema};
+
+pub mod json_schema;
+pub use json_schema::{JsonSchema, DIALECT};
+pub mod validation;
+pub use validation::{Validate, Violation};
+pub mod error;
+pub use error::{DecodeError, ErrorKind, SchemaError};
+pub mod formats;
+pub use formats::FormatError;
+pub mod patch;
+pub use patch::{PatchError, PatchOperation, Patchable};
+pub mod registry;
+pub use registry::{DynSchema, Registry};
+pub mod migration;
+pub use migration::Migration;
+pub mod surreal;
+pub mod typescript;
+#[cfg(feature = "fake")]
+pub mod fake;
+
 use serde_json::Error as JsonError;
 use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
 
 /// `Schema` is a trait that provides methods for converting a type to and from JSON.
+/// Its JSON Schema comes from the [`JsonSchema`] supertrait, through `json_schema`, and its validation
+/// rules from the [`Validate`] supertrait, through `validate`.
 ///
 /// # Methods
 ///
-/// `to_json`: Converts the type implementing this trait into a JSON string.
+/// `to_json`: Converts the type implementing this trait into a JSON string, stamped with its version as `"$v"`
+/// if the schema is versioned (`from_json` then upgrades older documents with the schema's migrations).
 /// Returns a `Result` which is an `Ok` of the JSON string, or an `Err` of `JsonError` if the conversion fails.
 ///
 /// `from_json`: Converts a JSON string into a type implementing this trait.
-/// Returns a `Result` which is an `Ok` of the type, or an `Err` of `JsonError` if the conversion fails.
+/// Returns a `Result` which is an `Ok` of the type, or an `Err` of `SchemaError` if the conversion fails,
+/// with the JSON pointer of the failing value.
+///
+/// `merge_patch` and `json_patch`: Apply a JSON Merge Patch (RFC 7386) or a JSON Patch (RFC 6902) to a copy
+/// of the value, then decode and validate it. [`patch::diff`] gives the JSON Patch between two values.
+///
+/// `surreal_table`: The SurrealDB table storing the values, whose `migrate` keeps its `SCHEMAFULL` definitions
+/// in sync with the schema.
+///
+/// `to_msgpack`/`from_msgpack`, `to_cbor`/`from_cbor` and `to_yaml`/`from_yaml` do the same with other wire
+/// formats, each behind its cargo feature (`msgpack`, `cbor`, `yaml`), and fail with a [`FormatError`].
+///
+/// `fake`: A random valid value, for fixtures and load tests, behind the `fake` feature. The `proptest` feature
+/// adds `fake::strategy`, to property-test any schema.
 ///
 /// # Type Parameters
 ///
 /// `Self`: The type implementing this trait. It must also implement the `Serialize` and `Deserialize` traits.
-pub trait Schema {
+pub trait Schema: JsonSchema + Validate {
+    /// Version of the schema, under which it is registered in the [`Registry`].
+    const VERSION: u32 = 1;
+
+    /// Whether JSON documents carry their version, as `"$v"`, to be upgraded by [`MIGRATIONS`](Schema::MIGRATIONS).
+    const VERSIONED: bool = false;
+
+    /// Upgrades from older versions: the first from version 1 to 2, the second from 2 to 3, and so on.
+    const MIGRATIONS: &'static [Migration] = &[];
+
     /// Converts the type implementing this trait into a JSON string.
     ///
     /// # Returns
@@ -24,9 +71,21 @@ pub trait Schema {
         where
             Self: Serialize,
     {
+        if Self::VERSIONED {
+            return serde_json::to_value(self).map(|document| migration::stamp_current::<Self>(document).to_string());
+        }
+
         serde_json::to_string(self)
     }
 
+    /// Name of the schema in errors: the `title` of its JSON Schema, or else the name of the type.
+    fn schema_name() -> String {
+        match Self::subschema().get("title").and_then(serde_json::Value::as_str) {
+            Some(title) => title.to_string(),
+            None => std::any::type_name::<Self>().rsplit("::").next().unwrap_or_default().to_string(),
+        }
+    }
+
     /// Converts a JSON string into a type implementing this trait.
     ///
     /// # Arguments
@@ -35,18 +94,224 @@ pub trait Schema {
     ///
     /// # Returns
     ///
-    /// A `Result` which is an `Ok` of the type, or an `Err` of `JsonError` if the conversion fails.
-    fn from_json<'b>(json: &'b str) -> Result<Self, JsonError>
+    /// A `Result` which is an `Ok` of the type, or an `Err` of `SchemaError` if the conversion fails,
+    /// telling where and why.
+    #[allow(clippy::result_large_err)] // Errors are rare, and end up in responses
+    fn from_json<'b>(json: &'b str) -> Result<Self, SchemaError>
+        where
+            Self: Deserialize<'b>,
+    {
+        if Self::VERSIONED {
+            return Self::from_document(migration::parse::<Self>(json)?);
+        }
+
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let mut track = serde_path_to_error::Track::new();
+
+        let value = Self::deserialize(serde_path_to_error::Deserializer::new(&mut deserializer, &mut track))
+            .and_then(|value| deserializer.end().map(|_| value))
+            .map_err(|error| SchemaError::json(&Self::schema_name(), &Self::subschema(), &track.path(), &error))?;
+
+        Ok(value)
+    }
+
+    /// Decodes a document of a versioned schema, read from any format, once upgraded (see [`migration::from_document`]).
+    /// The derive implements it for versioned schemas, bound on `DeserializeOwned`.
+    #[doc(hidden)]
+    #[allow(clippy::result_large_err)]
+    fn from_document(document: serde_json::Value) -> Result<Self, SchemaError>
+        where
+            Self: Sized,
+    {
+        let _ = document;
+        Err(migration::error::<Self>(String::from("versioned schemas must derive Schema")))
+    }
+
+    /// Converts a JSON string into a type implementing this trait, then checks its validation rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - A JSON string to be converted into the type.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is an `Ok` of the type, or an `Err` of `DecodeError` with either the decoding error
+    /// or every rule the value breaks, as `SchemaError`s.
+    #[allow(clippy::result_large_err)]
+    fn from_valid_json<'b>(json: &'b str) -> Result<Self, DecodeError>
+        where
+            Self: Deserialize<'b>,
+    {
+        let value = Self::from_json(json)?;
+        value.validate().map_err(|violations| {
+            let name = Self::schema_name();
+            DecodeError::Invalid(violations.iter().map(|violation| SchemaError::violation(&name, violation)).collect())
+        })?;
+
+        Ok(value)
+    }
+
+    /// Applies a JSON Merge Patch (RFC 7386) to a copy of the value.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is an `Ok` of the patched value, or an `Err` of `PatchError` if it isn't valid.
+    #[allow(clippy::result_large_err)]
+    fn merge_patch(&self, patch: &serde_json::Value) -> Result<Self, PatchError>
+        where
+            Self: Serialize + DeserializeOwned,
+    {
+        let mut document = serde_json::to_value(self).map_err(PatchError::Serialize)?;
+        patch::merge(&mut document, patch);
+
+        Self::from_valid_json(&migration::stamp_current::<Self>(document).to_string()).map_err(PatchError::Decode)
+    }
+
+    /// Applies the operations of a JSON Patch (RFC 6902) to a copy of the value.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is an `Ok` of the patched value, or an `Err` of `PatchError` if an operation fails
+    /// or the result isn't valid.
+    #[allow(clippy::result_large_err)]
+    fn json_patch(&self, operations: &[PatchOperation]) -> Result<Self, PatchError>
+        where
+            Self: Serialize + DeserializeOwned,
+    {
+        let mut document = serde_json::to_value(self).map_err(PatchError::Serialize)?;
+        patch::apply(&mut document, operations)?;
+
+        Self::from_valid_json(&migration::stamp_current::<Self>(document).to_string()).map_err(PatchError::Decode)
+    }
+
+    /// SurrealDB table `name` storing values of this schema, whose definitions come from its JSON Schema.
+    fn surreal_table(name: &str) -> surreal::Table {
+        surreal::Table::new::<Self>(name)
+    }
+
+    /// Converts the type implementing this trait into MessagePack, with its fields named as in JSON.
+    #[cfg(feature = "msgpack")]
+    fn to_msgpack(&self) -> Result<Vec<u8>, FormatError>
+        where
+            Self: Serialize,
+    {
+        if Self::VERSIONED {
+            return rmp_serde::to_vec_named(&migration::stamp_current::<Self>(serde_json::to_value(self)?)).map_err(FormatError::MsgPackEncode);
+        }
+
+        rmp_serde::to_vec_named(self).map_err(FormatError::MsgPackEncode)
+    }
+
+    /// Converts MessagePack into a type implementing this trait.
+    #[cfg(feature = "msgpack")]
+    fn from_msgpack(bytes: &[u8]) -> Result<Self, FormatError>
+        where
+            Self: DeserializeOwned,
+    {
+        if Self::VERSIONED {
+            let document = rmp_serde::from_slice(bytes).map_err(FormatError::MsgPackDecode)?;
+            return Self::from_document(document).map_err(|error| FormatError::Schema("msgpack", Box::new(error)));
+        }
+
+        rmp_serde::from_slice(bytes).map_err(FormatError::MsgPackDecode)
+    }
+
+    /// Converts the type implementing this trait into CBOR.
+    #[cfg(feature = "cbor")]
+    fn to_cbor(&self) -> Result<Vec<u8>, FormatError>
+        where
+            Self: Serialize,
+    {
+        let mut bytes = Vec::new();
+        if Self::VERSIONED {
+            let document = migration::stamp_current::<Self>(serde_json::to_value(self)?);
+            ciborium::into_writer(&document, &mut bytes).map_err(FormatError::CborEncode)?;
+        } else {
+            ciborium::into_writer(self, &mut bytes).map_err(FormatError::CborEncode)?;
+        }
+
+        Ok(bytes)
+    }
+
+    /// Converts CBOR into a type implementing this trait.
+    #[cfg(feature = "cbor")]
+    fn from_cbor(bytes: &[u8]) -> Result<Self, FormatError>
+        where
+            Self: DeserializeOwned,
+    {
+        if Self::VERSIONED {
+            let document = ciborium::from_reader(bytes).map_err(FormatError::CborDecode)?;
+            return Self::from_document(document).map_err(|error| FormatError::Schema("cbor", Box::new(error)));
+        }
+
+        ciborium::from_reader(bytes).map_err(FormatError::CborDecode)
+    }
+
+    /// Converts the type implementing this trait into a YAML string.
+    #[cfg(feature = "yaml")]
+    fn to_yaml(&self) -> Result<String, FormatError>
+        where
+            Self: Serialize,
+    {
+        if Self::VERSIONED {
+            return serde_yaml::to_string(&migration::stamp_current::<Self>(serde_json::to_value(self)?)).map_err(FormatError::Yaml);
+        }
+
+        serde_yaml::to_string(self).map_err(FormatError::Yaml)
+    }
+
+    /// Converts a YAML string into a type implementing this trait.
+    #[cfg(feature = "yaml")]
+    fn from_yaml<'b>(yaml: &'b str) -> Result<Self, FormatError>
         where
             Self: Deserialize<'b>,
     {
-        serde_json::from_str(json)
+        if Self::VERSIONED {
+            let document = serde_yaml::from_str(yaml).map_err(FormatError::Yaml)?;
+            return Self::from_document(document).map_err(|error| FormatError::Schema("yaml", Box::new(error)));
+        }
+
+        serde_yaml::from_str(yaml).map_err(FormatError::Yaml)
+    }
+
+    /// A random value of this schema, whose strings, numbers and lengths follow its validation rules.
+    #[cfg(feature = "fake")]
+    fn fake<R: rand::Rng + ?Sized>(rng: &mut R) -> Self
+        where
+            Self: DeserializeOwned,
+    {
+        fake::fake(rng)
     }
 }
 
-/// `schema` is a macro that simplifies the process of defining a struct that implements the `Schema` trait.
+/// `schema` is a macro that simplifies the process of defining a struct or an enum that implements the `Schema` trait.
 /// It takes a struct name and a list of fields as input and automatically defines the struct with the specified fields.
-/// Each field is annotated with `#[serde(rename = $json_name)]` to specify the name of the field when it is serialized or deserialized.
+/// Each field can be annotated with `as $json_name`, which becomes `#[serde(rename = $json_name)]`, to specify
+/// the name of the field when it is serialized or deserialized.
+/// The struct's JSON Schema, from `json_schema()`, uses these names too.
+///
+/// Fields can also declare validation rules after `where`, separated by spaces, which `validate()` checks:
+/// `email`, `url`, `date`, `range(0..=130)`, `len(1..=200)` and `each(len(..=32))` for every item of a list.
+///
+/// With `enum`, it defines a tagged union instead. Variants are unit, newtype (`Name(Type)`) or have fields
+/// declared like a struct's, and can be renamed with `as $json_name` too.
+///
+/// Options are written as attributes before the name and become serde options: `#[tag = "type"]` tags
+/// enums internally, adding `#[content = "data"]` tags them adjacently, `#[untagged]` doesn't tag them,
+/// and `#[rename_all = "snake_case"]` renames every variant of an enum or every field of a struct, so their
+/// names need no `as`. `#[strict]` rejects unknown fields instead of ignoring them. `#[builder]` adds
+/// `Name::builder()` to a struct, whose `build()` fails on missing required fields and broken rules.
+/// `#[version = 3]` versions the schema: it is registered under this version in the [`Registry`] (which every
+/// schema joins), its JSON documents carry it as `"$v"`, and `#[migrations(v1_to_v2, v2_to_v3)]` upgrades older
+/// documents (those without `"$v"` being version 1) when they are decoded, from any format. Each schema gets a test
+/// round-tripping values through every wire format: `#[sample = <expr>]`, and random ones with the `fake` feature,
+/// or else its default value (enums have none). `#[round_trip = false]` turns it off, for those declared in a function.
+///
+/// Every struct also gets a `<Name>Patch` struct, whose fields are all optional (`Option` fields become
+/// `Option<Option<T>>`, where `Some(None)` is `null` and clears the field), for [`Patchable::apply_patch`].
+///
+/// Fields of a struct can end with `= value`, their default: it fills the field when it is missing from
+/// the JSON, and in `Default::default()`. Other fields default to their type's default.
 ///
 /// # Arguments
 ///
@@ -54,31 +319,135 @@ pub trait Schema {
 /// * `$field_name`: The name of a field in the struct.
 /// * `$field_type`: The type of the field.
 /// * `$json_name`: The name of the field when it is serialized or deserialized.
+/// * `$rule`: A validation rule of the field.
+/// * `$default`: The default value of the field.
+/// * `$variant`: The name of a variant of the enum.
+///
+/// # Example
+///
+/// ```rust, ignore
+/// schema!(
+///     Profile {
+///         email: String as "email" where email,
+///         age: u8 as "age" where range(0..=130),
+///         title: String where len(1..=200),
+///         tags: Vec<String> where each(len(..=32)),
+///     }
+/// );
+///
+/// schema!(
+///     #[strict]
+///     #[rename_all = "camelCase"]
+///     SearchRequest {
+///         question: String where len(1..),
+///         top_k: u32 where range(1..=50) = 5,
+///     }
+/// );
+///
+/// schema!(
+///     #[tag = "role"]
+///     #[rename_all = "snake_case"]
+///     enum ChatMessage {
+///         User { content: String },
+///         ToolCall { name: String, arguments: serde_json::Value as "args" },
+///         Empty as "none",
+///     }
+/// );
+/// ```
 #[macro_export]
 macro_rules! schema {
+    (@options [$($done:tt)*] #[doc = $doc:literal] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[doc = $doc]] $($rest)*);
+    };
+    (@options [$($done:tt)*] #[strict] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[serde(deny_unknown_fields)]] $($rest)*);
+    };
+    (@options [$($done:tt)*] #[builder] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[schema(builder)]] $($rest)*);
+    };
+    (@options [$($done:tt)*] #[version = $version:literal] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[schema(version = $version)]] $($rest)*);
+    };
+    (@options [$($done:tt)*] #[migrations($($migration:path),+ $(,)?)] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[schema(migrations($($migration),+))]] $($rest)*);
+    };
+    (@options [$($done:tt)*] #[sample = $sample:expr] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[schema(sample = $sample)]] $($rest)*);
+    };
+    (@options [$($done:tt)*] #[round_trip = $round_trip:literal] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[schema(round_trip = $round_trip)]] $($rest)*);
+    };
+    (@options [$($done:tt)*] #[untagged] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[serde(untagged)]] $($rest)*);
+    };
+    (@options [$($done:tt)*] #[$option:ident = $value:literal] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[serde($option = $value)]] $($rest)*);
+    };
+    (@options [$($done:tt)*] #[$meta:meta] $($rest:tt)*) => {
+        $crate::schema!(@options [$($done)* #[$meta]] $($rest)*);
+    };
+    (
+        @options [$(#[$attr:meta])*]
+        enum $name:ident {
+            $(
+                $variant:ident
+                $({
+                    $(
+                        $field_name:ident : $field_type:ty $(as $json_name:literal)? $(where $($rule:ident $(($($args:tt)*))?)+)?
+                    ),*$(,)*
+                })?
+                $(($inner:ty))?
+                $(as $variant_json:literal)?
+            ),*$(,)*
+        }
+    ) => {
+        #[derive(Debug, Clone, PartialEq, $crate::schemas::Schema, ::serde::Serialize, ::serde::Deserialize)]
+        $(#[$attr])*
+        pub enum $name {
+            $(
+                $(#[serde(rename = $variant_json)])?
+                $variant
+                $({
+                    $(
+                        $(#[serde(rename = $json_name)])?
+                        $(#[validate($($rule $(($($args)*))?),+)])?
+                        $field_name: $field_type,
+                    )*
+                })?
+                $(($inner))?,
+            )*
+        }
+    };
     (
+        @options [$(#[$attr:meta])*]
         $name:ident {
             $(
-                $field_name:ident : $field_type:ty as $json_name:expr
+                $field_name:ident : $field_type:ty $(as $json_name:literal)? $(where $($rule:ident $(($($args:tt)*))?)+)?
+                $(= $default:expr)?
             ),*$(,)*
         }
     ) => {
-        use serde::{Serialize, Deserialize};
-        use crate::schemas::Schema;
-        use serde_json::Error as JsonError;
-
-        #[derive(Debug, Clone, Default, PartialEq, schema_macro::Schema, Serialize, Deserialize)]
+        #[$crate::schemas::patch]
+        #[$crate::schemas::defaults]
+        #[derive(Debug, Clone, PartialEq, $crate::schemas::Schema, ::serde::Serialize, ::serde::Deserialize)]
+        $(#[$attr])*
         pub struct $name {
             $(
-                #[serde(rename = $json_name)]
+                $(#[serde(rename = $json_name)])?
+                $(#[validate($($rule $(($($args)*))?),+)])?
+                $(#[default_value($default)])?
                 pub $field_name: $field_type,
             )*
         }
     };
+    ($($tokens:tt)*) => {
+        $crate::schema!(@options [] $($tokens)*);
+    };
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::schemas::{ErrorKind, JsonSchema, Schema, SchemaError};
 
     schema!(
         TestStruct {
@@ -134,10 +503,30 @@ mod tests {
     fn test_deserialization_without_enough_fields_fails_correctly() {
         let data = r#"{"field1":"Hello, world!"}"#;
 
-        let result: Result<TestStruct, JsonError> = TestStruct::from_json(data);
+        let result: Result<TestStruct, SchemaError> = TestStruct::from_json(data);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_schema_errors() {
+        let error = TestStruct::from_json(r#"{"field1":"Hello, world!"}"#).unwrap_err();
+        assert_eq!(error, SchemaError {
+            schema: String::from("TestStruct"),
+            path: String::from("/field2"),
+            kind: ErrorKind::MissingField,
+            expected: Some(String::from("integer")),
+            rule: None,
+            message: String::from("missing field `field2`"),
+        });
+
+        let error = TestStruct::from_json(r#"{"field1":"Hello, world!","field2":"42"}"#).unwrap_err();
+        assert_eq!((error.path.as_str(), error.kind, error.expected.as_deref()), ("/field2", ErrorKind::InvalidType, Some("integer")));
+
+        let error = TestStruct::from_json(r#"{"field1":"#).unwrap_err();
+        assert_eq!((error.kind, error.expected), (ErrorKind::Syntax, None));
+        assert_eq!(TestStruct::from_json(r#"{"field1":"","field2":1} x"#).unwrap_err().kind, ErrorKind::Syntax);
+    }
+
     #[test]
     fn test_deserialization_with_extra_fields_works_correctly() {
         let data = r#"{"field1":"Hello, world!","field2":42,"field3":"Optional field","extra_field":"Extra field"}"#;
@@ -151,4 +540,548 @@ mod tests {
 
         assert_eq!(test_struct, expected);
     }
+
+    #[test]
+    fn test_json_schema() {
+        let expected = serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "TestStruct",
+            "type": "object",
+            "properties": {
+                "field1": { "type": "string" },
+                "field2": { "type": "integer", "minimum": i32::MIN, "maximum": i32::MAX },
+                "field3": { "type": ["string", "null"] },
+            },
+            "required": ["field1", "field2"],
+        });
+
+        assert_eq!(TestStruct::json_schema(), expected);
+    }
+
+    mod nested {
+        use crate::schemas::JsonSchema;
+        schema!(
+            Conversation {
+                title: String as "title",
+                messages: Vec<super::TestStruct> as "messages",
+            }
+        );
+
+        #[test]
+        fn test_nested_json_schema() {
+            let schema = Conversation::json_schema();
+
+            assert_eq!(schema["properties"]["messages"]["type"], "array");
+            assert_eq!(schema["properties"]["messages"]["items"]["title"], "TestStruct");
+            assert_eq!(schema["properties"]["messages"]["items"].get("$schema"), None);
+        }
+    }
+
+    mod validation {
+        use serde_json::json;
+
+        use crate::schemas::{DecodeError, ErrorKind, JsonSchema, Schema, Validate, Violation};
+
+        schema!(
+            Profile {
+                email: String as "email" where email,
+                age: u8 as "age" where range(0..=130),
+                title: String where len(1..=200),
+                tags: Vec<String> where each(len(..=32)),
+                website: Option<String> as "site" where url len(..=100),
+            }
+        );
+
+        schema!(
+            Team {
+                members: Vec<Profile> as "members" where len(1..),
+            }
+        );
+
+        fn profile() -> Profile {
+            Profile {
+                email: String::from("ana@doctour.ai"),
+                age: 30,
+                title: String::from("Engineer"),
+                tags: vec![String::from("rust")],
+                website: None,
+            }
+        }
+
+        #[test]
+        fn test_valid() {
+            assert_eq!(profile().validate(), Ok(()));
+            assert_eq!(Profile { website: Some(String::from("https://doctour.ai")), ..profile() }.validate(), Ok(()));
+        }
+
+        #[test]
+        fn test_json_schema_keywords() {
+            let schema = Profile::json_schema();
+
+            assert_eq!(schema["properties"]["email"], json!({ "type": "string", "format": "email" }));
+            assert_eq!(schema["properties"]["age"], json!({ "type": "integer", "minimum": 0, "maximum": 130 }));
+            assert_eq!(schema["properties"]["tags"]["items"], json!({ "type": "string", "maxLength": 32 }));
+            assert_eq!(schema["properties"]["site"], json!({ "type": ["string", "null"], "format": "uri", "maxLength": 100 }));
+            assert_eq!(Team::json_schema()["properties"]["members"]["minItems"], 1);
+        }
+
+        #[test]
+        fn test_every_violation_is_reported() {
+            let profile = Profile {
+                email: String::from("ana"),
+                age: 200,
+                title: String::new(),
+                tags: vec![String::from("ok"), "x".repeat(33)],
+                website: Some(String::from("doctour.ai")),
+            };
+
+            let violations = profile.validate().unwrap_err();
+            let paths: Vec<(&str, &str)> = violations.iter()
+                .map(|violation| (violation.path.as_str(), violation.rule.as_str()))
+                .collect();
+
+            assert_eq!(paths, vec![("/email", "email"), ("/age", "range"), ("/title", "len"), ("/tags/1", "len"), ("/site", "url")]);
+            assert_eq!(violations[1].message, "must be in 0..=130");
+        }
+
+        #[test]
+        fn test_nested_violations() {
+            let team = Team { members: vec![profile(), Profile { age: 131, ..profile() }] };
+            assert_eq!(team.validate(), Err(vec![Violation::new("/members/1/age", "range", "must be in 0..=130")]));
+
+            assert_eq!(Team { members: vec![] }.validate().unwrap_err()[0].path, "/members");
+        }
+
+        #[test]
+        fn test_nested_schema_error() {
+            let json = r#"{"members":[{"email":"ana@doctour.ai","age":30,"title":"Engineer","tags":[1]}]}"#;
+            let error = Team::from_json(json).unwrap_err();
+
+            assert_eq!(error.schema, "Team");
+            assert_eq!(error.path, "/members/0/tags/0");
+            assert_eq!((error.kind, error.expected.as_deref()), (ErrorKind::InvalidType, Some("string")));
+        }
+
+        #[test]
+        fn test_from_valid_json() {
+            let valid = r#"{"email":"ana@doctour.ai","age":30,"title":"Engineer","tags":[]}"#;
+            assert_eq!(Profile::from_valid_json(valid).unwrap().age, 30);
+
+            let invalid = r#"{"email":"ana@doctour.ai","age":30,"title":"","tags":[]}"#;
+            assert!(Profile::from_json(invalid).is_ok());
+            match Profile::from_valid_json(invalid) {
+                Err(DecodeError::Invalid(errors)) => {
+                    assert_eq!(errors[0].path, "/title");
+                    assert_eq!((errors[0].kind, errors[0].rule.as_deref()), (ErrorKind::Validation, Some("len")));
+                    assert_eq!(errors[0].schema, "Profile");
+                }
+                other => panic!("Expected a validation error, got {:?}", other),
+            }
+
+            let error = Profile::from_valid_json(r#"{"email":"ana@doctour.ai","age":30,"tags":[]}"#).unwrap_err();
+            assert!(matches!(error, DecodeError::Json(_)));
+            assert_eq!(error.errors()[0].path, "/title");
+            assert_eq!(error.errors()[0].kind, ErrorKind::MissingField);
+        }
+    }
+
+    mod options {
+        use serde_json::json;
+        use crate::schemas::{ErrorKind, JsonSchema, Schema, Validate};
+
+        schema!(
+            #[strict]
+            #[rename_all = "camelCase"]
+            SearchRequest {
+                question: String where len(1..),
+                top_k: u32 where range(1..=50) = 5,
+                collection_name: String as "collection" = String::from("docs"),
+                with_sources: bool,
+            }
+        );
+
+        #[test]
+        fn test_defaults() {
+            let request = SearchRequest::from_json(r#"{"question":"What is Rust?","withSources":true}"#).unwrap();
+
+            assert_eq!(request.top_k, 5);
+            assert_eq!(request.collection_name, "docs");
+            assert_eq!(request.validate(), Ok(()));
+            assert_eq!(SearchRequest::default(), SearchRequest {
+                question: String::new(),
+                top_k: 5,
+                collection_name: String::from("docs"),
+                with_sources: false,
+            });
+            assert_eq!(SearchRequest::from_json(r#"{"question":"?","topK":8,"withSources":false}"#).unwrap().top_k, 8);
+        }
+
+        #[test]
+        fn test_strict() {
+            let error = SearchRequest::from_json(r#"{"questoin":"What is Rust?","withSources":true}"#).unwrap_err();
+            assert_eq!((error.path.as_str(), error.kind), ("/questoin", ErrorKind::UnknownField));
+            assert!(SearchRequest::from_json(r#"{"question":"?","withSources":true,"top_k":5}"#).is_err());
+        }
+
+        #[test]
+        fn test_options_json_schema() {
+            let schema = SearchRequest::json_schema();
+
+            assert_eq!(schema["additionalProperties"], false);
+            assert_eq!(schema["required"], json!(["question", "withSources"]));
+            assert_eq!(schema["properties"]["topK"]["default"], 5);
+            assert_eq!(schema["properties"]["collection"]["default"], "docs");
+            assert_eq!(schema["properties"]["question"].get("default"), None);
+        }
+    }
+
+    mod patches {
+        use serde_json::json;
+        use crate::schemas::{patch, PatchError, PatchOperation, Patchable, Schema};
+
+        schema!(
+            #[rename_all = "camelCase"]
+            DocumentMetadata {
+                title: String where len(1..),
+                tags: Vec<String>,
+                summary: Option<String>,
+                page_count: u32,
+            }
+        );
+
+        fn metadata() -> DocumentMetadata {
+            DocumentMetadata {
+                title: String::from("Rust"),
+                tags: vec![String::from("book")],
+                summary: Some(String::from("The book")),
+                page_count: 500,
+            }
+        }
+
+        #[test]
+        fn test_apply_patch() {
+            let patch = DocumentMetadataPatch::from_json(r#"{"pageCount":550,"summary":null}"#).unwrap();
+            assert_eq!(patch, DocumentMetadataPatch { page_count: Some(550), summary: Some(None), ..Default::default() });
+            assert_eq!(patch.to_json().unwrap(), r#"{"summary":null,"pageCount":550}"#);
+
+            let mut metadata = metadata();
+            metadata.apply_patch(patch);
+            assert_eq!(metadata, DocumentMetadata { page_count: 550, summary: None, ..self::metadata() });
+
+            metadata.apply_patch(DocumentMetadataPatch::default());
+            assert_eq!(metadata.page_count, 550);
+        }
+
+        #[test]
+        fn test_merge_patch() {
+            let patched = metadata().merge_patch(&json!({ "title": "Rust 2024", "summary": null })).unwrap();
+            assert_eq!(patched, DocumentMetadata { title: String::from("Rust 2024"), summary: None, ..metadata() });
+
+            match metadata().merge_patch(&json!({ "title": "" })) {
+                Err(PatchError::Decode(error)) => assert_eq!(error.errors()[0].path, "/title"),
+                other => panic!("Expected a validation error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_json_patch() {
+            let operations: Vec<PatchOperation> = serde_json::from_value(json!([
+                { "op": "add", "path": "/tags/-", "value": "rust" },
+                { "op": "replace", "path": "/pageCount", "value": 560 },
+            ])).unwrap();
+            let patched = metadata().json_patch(&operations).unwrap();
+
+            assert_eq!(patched.tags, vec!["book", "rust"]);
+            assert_eq!(patched.page_count, 560);
+            assert!(matches!(metadata().json_patch(&[PatchOperation::Remove { path: String::from("/title") }]),
+                             Err(PatchError::Decode(_))));
+        }
+
+        #[test]
+        fn test_diff() {
+            let (from, to) = (metadata(), DocumentMetadata { summary: None, page_count: 1, ..metadata() });
+            let operations = patch::diff(&from, &to).unwrap();
+
+            assert_eq!(operations, vec![
+                PatchOperation::Replace { path: String::from("/pageCount"), value: json!(1) },
+                PatchOperation::Replace { path: String::from("/summary"), value: json!(null) },
+            ]);
+            assert_eq!(from.json_patch(&operations).unwrap(), to);
+            assert_eq!(patch::diff(&from, &from).unwrap(), vec![]);
+        }
+    }
+
+    mod builders {
+        use crate::schemas::{ErrorKind, SchemaError};
+
+        schema!(
+            #[builder]
+            ChatRequest {
+                question: String where len(1..),
+                conversation_id: Option<String> as "conversation",
+                top_k: u32 where range(1..=50) = 5,
+                history: Vec<String>,
+            }
+        );
+
+        #[test]
+        fn test_build() {
+            let request = ChatRequest::builder()
+                .question("What is Rust?")
+                .conversation_id("c-1")
+                .history(vec![String::from("Hi")])
+                .build()
+                .unwrap();
+
+            assert_eq!(request, ChatRequest {
+                question: String::from("What is Rust?"),
+                conversation_id: Some(String::from("c-1")),
+                top_k: 5,
+                history: vec![String::from("Hi")],
+            });
+        }
+
+        #[test]
+        fn test_missing_fields() {
+            let errors = ChatRequest::builder().top_k(8u32).build().unwrap_err();
+
+            assert_eq!(errors.len(), 2);
+            assert_eq!(errors[0], SchemaError {
+                schema: String::from("ChatRequest"),
+                path: String::from("/question"),
+                kind: ErrorKind::MissingField,
+                expected: Some(String::from("string")),
+                rule: None,
+                message: String::from("missing field `question`"),
+            });
+            assert_eq!((errors[1].path.as_str(), errors[1].expected.as_deref()), ("/history", Some("array")));
+        }
+
+        #[test]
+        fn test_build_validates() {
+            let errors = ChatRequest::builder().question("").top_k(51u32).history(vec![]).build().unwrap_err();
+            let paths: Vec<(&str, ErrorKind)> = errors.iter().map(|error| (error.path.as_str(), error.kind)).collect();
+
+            assert_eq!(paths, vec![("/question", ErrorKind::Validation), ("/top_k", ErrorKind::Validation)]);
+        }
+    }
+
+    mod unions {
+        use serde_json::json;
+        use crate::schemas::formats::assert_round_trip;
+        use crate::schemas::{JsonSchema, Schema, Validate, Violation};
+
+        schema!(
+            #[tag = "role"]
+            #[rename_all = "snake_case"]
+            #[sample = ChatMessage::ToolCall { name: String::from("search"), arguments: json!({ "q": [1, 2.5] }) }]
+            enum ChatMessage {
+                User { content: String where len(1..) },
+                Assistant { content: String, model: Option<String> },
+                ToolCall { name: String, arguments: serde_json::Value as "args" },
+                ToolResult { call_id: String as "id", output: String } as "tool",
+                Empty,
+            }
+        );
+
+        #[test]
+        fn test_internal_tagging() {
+            let message = ChatMessage::ToolCall { name: String::from("search"), arguments: json!({ "q": "rust" }) };
+            let json = message.to_json().unwrap();
+
+            assert_eq!(json, r#"{"role":"tool_call","name":"search","args":{"q":"rust"}}"#);
+            assert_eq!(ChatMessage::from_json(&json).unwrap(), message);
+            assert_eq!(ChatMessage::from_json(r#"{"role":"tool","id":"1","output":"ok"}"#).unwrap(),
+                       ChatMessage::ToolResult { call_id: String::from("1"), output: String::from("ok") });
+            assert!(ChatMessage::from_json(r#"{"role":"system","content":"hi"}"#).is_err());
+        }
+
+        #[test]
+        fn test_union_json_schema() {
+            let schema = ChatMessage::json_schema();
+            let variants = schema["oneOf"].as_array().unwrap();
+
+            assert_eq!(schema["title"], "ChatMessage");
+            assert_eq!(variants.len(), 5);
+            assert_eq!(variants[0], json!({
+                "type": "object",
+                "properties": { "role": { "const": "user" }, "content": { "type": "string", "minLength": 1 } },
+                "required": ["role", "content"],
+            }));
+            assert_eq!(variants[3]["properties"]["role"]["const"], "tool");
+            assert_eq!(variants[4], json!({ "type": "object", "properties": { "role": { "const": "empty" } }, "required": ["role"] }));
+        }
+
+        #[test]
+        fn test_union_validation() {
+            assert_eq!(ChatMessage::User { content: String::from("hi") }.validate(), Ok(()));
+            assert_eq!(ChatMessage::User { content: String::new() }.validate(),
+                       Err(vec![Violation::new("/content", "len", "length must be in 1..")]));
+        }
+
+        schema!(
+            #[tag = "kind"]
+            #[content = "data"]
+            #[sample = Event::Progress { done: 1, total: 2 }]
+            enum Event {
+                Started(u64) as "started",
+                Progress { done: u32, total: u32 } as "progress",
+                Finished as "finished",
+            }
+        );
+
+        #[test]
+        fn test_adjacent_tagging() {
+            let event = Event::Progress { done: 1, total: 2 };
+            assert_eq!(event.to_json().unwrap(), r#"{"kind":"progress","data":{"done":1,"total":2}}"#);
+            assert_eq!(Event::from_json(r#"{"kind":"started","data":7}"#).unwrap(), Event::Started(7));
+
+            let schema = Event::json_schema();
+            assert_eq!(schema["oneOf"][0]["required"], json!(["kind", "data"]));
+            assert_eq!(schema["oneOf"][0]["properties"]["data"]["type"], "integer");
+        }
+
+        schema!(
+            #[rename_all = "lowercase"]
+            #[sample = Priority::High]
+            enum Priority {
+                Low,
+                High,
+            }
+        );
+
+        #[test]
+        fn test_unit_enum() {
+            assert_eq!(Priority::High.to_json().unwrap(), r#""high""#);
+            assert_eq!(Priority::json_schema()["enum"], json!(["low", "high"]));
+        }
+
+        #[test]
+        fn test_round_trip() {
+            assert_round_trip(&ChatMessage::Assistant { content: String::from("hi"), model: Some(String::from("gpt")) });
+            assert_round_trip(&ChatMessage::ToolCall { name: String::from("search"), arguments: json!({ "q": [1, 2.5] }) });
+            assert_round_trip(&ChatMessage::Empty);
+            assert_round_trip(&Event::Progress { done: 1, total: 2 });
+            assert_round_trip(&Event::Finished);
+            assert_round_trip(&Priority::Low);
+        }
+
+        #[test]
+        fn test_local_schema() {
+            schema!(
+                #[round_trip = false]
+                Local { name: String = String::from("local") }
+            );
+
+            assert_round_trip(&Local::default());
+        }
+    }
+
+    mod generics {
+        use std::fmt::Debug;
+        use serde_json::json;
+        use crate::schemas::{JsonSchema, Schema, Validate};
+
+        #[derive(Debug, PartialEq, crate::schemas::Schema, serde::Serialize, serde::Deserialize)]
+        struct Page<T> {
+            #[validate(len(..=2))]
+            items: Vec<T>,
+            #[serde(rename = "next")]
+            next_cursor: Option<String>,
+        }
+
+        #[derive(Debug, crate::schemas::Schema, serde::Serialize, serde::Deserialize)]
+        struct Envelope<'a, T: Clone>
+            where
+                T: Debug,
+        {
+            #[serde(borrow)]
+            kind: &'a str,
+            payload: T,
+        }
+
+        #[test]
+        fn test_generic_schema() {
+            let page = Page { items: vec![super::TestStruct::default()], next_cursor: None };
+            let json = page.to_json().unwrap();
+
+            assert_eq!(Page::<super::TestStruct>::from_json(&json).unwrap(), page);
+            assert_eq!(Page::<u8>::json_schema()["properties"]["items"]["items"]["type"], "integer");
+            assert_eq!(Page::<u8>::json_schema()["required"], json!(["items"]));
+            assert_eq!(Page { items: vec![1, 2, 3], next_cursor: None }.validate().unwrap_err()[0].path, "/items");
+        }
+
+        #[test]
+        fn test_borrowed_schema() {
+            let envelope: Envelope<Vec<u8>> = serde_json::from_str(r#"{"kind":"bytes","payload":[1]}"#).unwrap();
+
+            assert_eq!(envelope.kind, "bytes");
+            assert_eq!(envelope.validate(), Ok(()));
+            assert_eq!(Envelope::<'_, bool>::json_schema()["properties"]["payload"], json!({ "type": "boolean" }));
+        }
+    }
+
+    mod recursive {
+        use serde_json::json;
+        use crate::schemas::{JsonSchema, Schema};
+
+        #[derive(Debug, Clone, PartialEq, Default, crate::schemas::Schema, serde::Serialize, serde::Deserialize)]
+        pub struct Section {
+            title: String,
+            subsections: Vec<Section>,
+            next: Option<Box<Section>>,
+            #[serde(skip_deserializing)]
+            words: u32,
+        }
+
+        #[test]
+        fn test_recursive_schema() {
+            let schema = Section::json_schema();
+
+            let anchor = "doctour_ai.schemas.tests.recursive.Section";
+            assert_eq!(schema["$anchor"], anchor);
+            assert_eq!(schema["properties"]["subsections"]["items"], json!({ "$ref": format!("#{}", anchor) }));
+            assert_eq!(schema["properties"]["next"], json!({ "anyOf": [{ "$ref": format!("#{}", anchor) }, { "type": "null" }] }));
+            assert_eq!(Vec::<Section>::subschema()["items"]["$anchor"], anchor);
+            assert_eq!(Option::<Section>::subschema()["anyOf"][0]["type"], "object");
+        }
+
+        mod other {
+            #[derive(Debug, Clone, PartialEq, Default, crate::schemas::Schema, serde::Serialize, serde::Deserialize)]
+            pub struct Section<T> {
+                value: T,
+                children: Vec<Section<T>>,
+            }
+        }
+
+        #[derive(Debug, Clone, PartialEq, Default, crate::schemas::Schema, serde::Serialize, serde::Deserialize)]
+        pub struct Book {
+            intro: Section,
+            appendix: Section,
+            notes: other::Section<u8>,
+        }
+
+        #[test]
+        fn test_unique_anchors() {
+            let schema = Book::json_schema();
+            let anchor = "doctour_ai.schemas.tests.recursive.Section";
+
+            assert_eq!(schema["properties"]["intro"]["$anchor"], anchor);
+            assert_eq!(schema["properties"]["appendix"], json!({ "$ref": format!("#{}", anchor) }));
+            assert_eq!(schema["properties"]["notes"]["$anchor"], "doctour_ai.schemas.tests.recursive.other.Section_u8_");
+            assert_eq!(schema["properties"]["notes"]["properties"]["children"]["items"],
+                       json!({ "$ref": "#doctour_ai.schemas.tests.recursive.other.Section_u8_" }));
+        }
+
+        #[test]
+        fn test_read_only_field() {
+            let schema = Section::json_schema();
+            assert_eq!(schema["properties"]["words"], json!({ "type": "integer", "minimum": 0, "maximum": u32::MAX, "readOnly": true }));
+            assert_eq!(schema["required"], json!(["title", "subsections"]));
+
+            let json = Section { words: 120, ..Section::default() }.to_json().unwrap();
+            assert!(json.contains(r#""words":120"#));
+            assert_eq!(Section::from_json(&json).unwrap().words, 0);
+        }
+    }
 }
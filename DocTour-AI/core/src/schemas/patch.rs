@@ -0,0 +1,297 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+use super::validation::pointer;
+use super::{DecodeError, Schema};
+
+/// Types with a partial version, `<Name>Patch`, whose fields are all optional: `schema!` structs.
+pub trait Patchable: Schema {
+    type Patch;
+
+    /// Sets every field given by `patch`, and leaves the others as they are. Doesn't validate.
+    fn apply_patch(&mut self, patch: Self::Patch);
+}
+
+/// Deserializes an `Option<T>` field of a patch as `Some(None)` when it is `null`, so it is told apart
+/// from a missing field, which is `None` with `#[serde(default)]`.
+pub fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+crate::schema!(
+    /// An operation of a JSON Patch (RFC 6902).
+    #[tag = "op"]
+    #[rename_all = "lowercase"]
+    #[sample = PatchOperation::Add { path: String::from("/tags/0"), value: Value::from("rust") }]
+    enum PatchOperation {
+        Add { path: String, value: Value },
+        Remove { path: String },
+        Replace { path: String, value: Value },
+        Move { from: String, path: String },
+        Copy { from: String, path: String },
+        Test { path: String, value: Value },
+    }
+);
+
+/// Error of a patch: an operation failed, or the patched document isn't a valid value of the schema.
+#[derive(Debug)]
+pub enum PatchError {
+    /// The value couldn't be turned into JSON.
+    Serialize(serde_json::Error),
+    /// The operation at `index` couldn't be applied.
+    Operation { index: usize, message: String },
+    Decode(DecodeError),
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::Serialize(error) => write!(f, "{}", error),
+            PatchError::Operation { index, message } => write!(f, "operation {}: {}", index, message),
+            PatchError::Decode(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Applies a JSON Merge Patch (RFC 7386) to `target`: objects are merged recursively, `null` removes a member,
+/// and any other value replaces the target.
+pub fn merge(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge(target.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Reference tokens of a JSON pointer, unescaped.
+fn tokens(path: &str) -> Result<Vec<String>, String> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !path.starts_with('/') {
+        return Err(format!("invalid pointer `{}`", path));
+    }
+
+    Ok(path[1..].split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+/// Parent of the value at `path`, and the value's last token, which must not be the root.
+fn parent<'a>(document: &'a mut Value, path: &str) -> Result<(&'a mut Value, String), String> {
+    let mut tokens = tokens(path)?;
+    let last = tokens.pop().ok_or_else(|| String::from("the root has no parent"))?;
+
+    let mut parent = document;
+    for token in tokens {
+        parent = match parent {
+            Value::Object(object) => object.get_mut(&token),
+            Value::Array(array) => token.parse::<usize>().ok().and_then(|index| array.get_mut(index)),
+            _ => None,
+        }.ok_or_else(|| format!("no value at `{}`", path))?;
+    }
+
+    Ok((parent, last))
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+
+    let (parent, token) = parent(document, path)?;
+    match parent {
+        Value::Object(object) => {
+            object.insert(token, value);
+        }
+        Value::Array(array) if token == "-" => array.push(value),
+        Value::Array(array) => match token.parse::<usize>() {
+            Ok(index) if index <= array.len() => array.insert(index, value),
+            _ => return Err(format!("invalid index at `{}`", path)),
+        },
+        _ => return Err(format!("no container at `{}`", path)),
+    }
+
+    Ok(())
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, String> {
+    let (parent, token) = parent(document, path)?;
+
+    match parent {
+        Value::Object(object) => object.remove(&token),
+        Value::Array(array) => match token.parse::<usize>() {
+            Ok(index) if index < array.len() => Some(array.remove(index)),
+            _ => None,
+        },
+        _ => None,
+    }.ok_or_else(|| format!("no value at `{}`", path))
+}
+
+/// Applies the operations of a JSON Patch (RFC 6902) to `document`, in order. Stops at the first that fails,
+/// leaving the operations before it applied.
+#[allow(clippy::result_large_err)]
+pub fn apply(document: &mut Value, operations: &[PatchOperation]) -> Result<(), PatchError> {
+    for (index, operation) in operations.iter().enumerate() {
+        let result = match operation {
+            PatchOperation::Add { path, value } => add(document, path, value.clone()),
+            PatchOperation::Remove { path } => remove(document, path).map(|_| ()),
+            PatchOperation::Replace { path, value } => match document.pointer_mut(path) {
+                Some(target) => {
+                    *target = value.clone();
+                    Ok(())
+                }
+                None => Err(format!("no value at `{}`", path)),
+            },
+            PatchOperation::Move { from, path } if path.starts_with(&format!("{}/", from)) => {
+                Err(format!("can't move `{}` into itself", from))
+            }
+            PatchOperation::Move { from, path } => remove(document, from).and_then(|value| add(document, path, value)),
+            PatchOperation::Copy { from, path } => match document.pointer(from).cloned() {
+                Some(value) => add(document, path, value),
+                None => Err(format!("no value at `{}`", from)),
+            },
+            PatchOperation::Test { path, value } => match document.pointer(path) {
+                Some(target) if target == value => Ok(()),
+                _ => Err(format!("test of `{}` failed", path)),
+            },
+        };
+
+        result.map_err(|message| PatchError::Operation { index, message })?;
+    }
+
+    Ok(())
+}
+
+/// Operations of a JSON Patch turning `from` into `to`, at the JSON pointer `path`.
+fn diff_values(from: &Value, to: &Value, path: &str, operations: &mut Vec<PatchOperation>) {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            // Sorted, as maps keep the order of their keys with serde_json's `preserve_order`
+            let mut keys: Vec<&String> = from.keys().collect();
+            keys.sort();
+            for key in keys {
+                match to.get(key) {
+                    Some(other) => diff_values(&from[key], other, &pointer(path, key), operations),
+                    None => operations.push(PatchOperation::Remove { path: pointer(path, key) }),
+                }
+            }
+
+            let mut added: Vec<&String> = to.keys().filter(|key| !from.contains_key(*key)).collect();
+            added.sort();
+            for key in added {
+                operations.push(PatchOperation::Add { path: pointer(path, key), value: to[key].clone() });
+            }
+        }
+        (Value::Array(from), Value::Array(to)) if from.len() == to.len() => {
+            for (index, (value, other)) in from.iter().zip(to).enumerate() {
+                diff_values(value, other, &pointer(path, index), operations);
+            }
+        }
+        _ if from != to => operations.push(PatchOperation::Replace { path: path.to_string(), value: to.clone() }),
+        _ => {}
+    }
+}
+
+/// JSON Patch (RFC 6902) turning `from` into `to`, which [`Schema::json_patch`] applies.
+/// Arrays whose length changed are replaced as a whole. The operations on the members of an object are sorted
+/// by key: removals and replacements first, then additions.
+pub fn diff<T: Schema + Serialize>(from: &T, to: &T) -> Result<Vec<PatchOperation>, serde_json::Error> {
+    let mut operations = Vec::new();
+    diff_values(&serde_json::to_value(from)?, &serde_json::to_value(to)?, "", &mut operations);
+
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        // Examples of RFC 7386
+        let mut target = json!({ "title": "Goodbye!", "author": { "givenName": "John", "familyName": "Doe" }, "tags": ["example", "sample"] });
+        merge(&mut target, &json!({ "title": "Hello!", "author": { "familyName": null }, "tags": ["example"], "phoneNumber": "+01-123-456-7890" }));
+        assert_eq!(target, json!({ "title": "Hello!", "author": { "givenName": "John" }, "tags": ["example"], "phoneNumber": "+01-123-456-7890" }));
+
+        let mut target = json!({ "a": "b" });
+        merge(&mut target, &json!({ "a": { "bb": { "ccc": null } } }));
+        assert_eq!(target, json!({ "a": { "bb": {} } }));
+
+        let mut target = json!(["a", "b"]);
+        merge(&mut target, &json!({ "a": "c" }));
+        assert_eq!(target, json!({ "a": "c" }));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut document = json!({ "foo": ["bar", "baz"], "a/b": 1 });
+        let operations: Vec<PatchOperation> = serde_json::from_value(json!([
+            { "op": "test", "path": "/a~1b", "value": 1 },
+            { "op": "add", "path": "/foo/1", "value": "qux" },
+            { "op": "add", "path": "/foo/-", "value": "end" },
+            { "op": "remove", "path": "/foo/0" },
+            { "op": "replace", "path": "/a~1b", "value": 2 },
+            { "op": "copy", "from": "/a~1b", "path": "/c" },
+            { "op": "move", "from": "/c", "path": "/d" },
+        ])).unwrap();
+
+        apply(&mut document, &operations).unwrap();
+        assert_eq!(document, json!({ "foo": ["qux", "baz", "end"], "a/b": 2, "d": 2 }));
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        let mut document = json!({ "foo": [1] });
+
+        let error = apply(&mut document, &[
+            PatchOperation::Remove { path: String::from("/foo/0") },
+            PatchOperation::Remove { path: String::from("/foo/0") },
+        ]).unwrap_err();
+        assert!(matches!(error, PatchError::Operation { index: 1, .. }));
+        assert_eq!(document, json!({ "foo": [] }));
+
+        assert!(apply(&mut document, &[PatchOperation::Test { path: String::from("/foo"), value: json!(1) }]).is_err());
+        assert!(apply(&mut document, &[PatchOperation::Move { from: String::from(""), path: String::from("/foo") }]).is_err());
+        assert!(apply(&mut document, &[PatchOperation::Add { path: String::from("/a/b"), value: json!(1) }]).is_err());
+    }
+
+    #[test]
+    fn test_diff_values() {
+        let (from, to) = (json!({ "a": 1, "b": [1, 2], "c": { "d": true } }), json!({ "b": [1, 3], "c": {}, "e": null }));
+        let mut operations = Vec::new();
+        diff_values(&from, &to, "", &mut operations);
+
+        assert_eq!(operations, vec![
+            PatchOperation::Remove { path: String::from("/a") },
+            PatchOperation::Replace { path: String::from("/b/1"), value: json!(3) },
+            PatchOperation::Remove { path: String::from("/c/d") },
+            PatchOperation::Add { path: String::from("/e"), value: Value::Null },
+        ]);
+
+        let mut document = from.clone();
+        apply(&mut document, &operations).unwrap();
+        assert_eq!(document, to);
+    }
+}
@@ -0,0 +1,265 @@
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Error as JsonError, Value};
+
+#[doc(hidden)]
+pub use inventory; // For the registrations of #[derive(Schema)]
+
+use super::{DecodeError, Schema, Violation};
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// A decoded value of a schema known only at runtime, from [`Registry::decode`].
+pub trait DynSchema: Any {
+    fn dyn_to_json(&self) -> Result<String, JsonError>;
+
+    fn dyn_to_value(&self) -> Result<Value, JsonError>;
+
+    fn dyn_validate(&self) -> Result<(), Vec<Violation>>;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Schema + Serialize + Any> DynSchema for T {
+    fn dyn_to_json(&self) -> Result<String, JsonError> {
+        self.to_json()
+    }
+
+    fn dyn_to_value(&self) -> Result<Value, JsonError> {
+        serde_json::to_value(self)
+    }
+
+    fn dyn_validate(&self) -> Result<(), Vec<Violation>> {
+        self.validate()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl dyn DynSchema {
+    /// The value, if it is a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+/// A registered schema, with its JSON Schema and its type-erased decoder.
+pub struct Entry {
+    pub name: &'static str,
+    pub version: u32,
+    /// Module of the type.
+    pub module: &'static str,
+    json_schema: fn() -> Value,
+    decode: fn(&str) -> Result<Box<dyn DynSchema>, DecodeError>,
+    #[cfg(feature = "fake")]
+    fake: fn(&mut dyn rand::RngCore) -> Box<dyn DynSchema>,
+}
+
+#[allow(clippy::result_large_err)]
+fn decode<T: Schema + Serialize + DeserializeOwned + Any>(json: &str) -> Result<Box<dyn DynSchema>, DecodeError> {
+    T::from_valid_json(json).map(|value| Box::new(value) as Box<dyn DynSchema>)
+}
+
+#[cfg(feature = "fake")]
+fn fake<T: Schema + Serialize + DeserializeOwned + Any>(rng: &mut dyn rand::RngCore) -> Box<dyn DynSchema> {
+    Box::new(super::fake::fake::<T, _>(rng))
+}
+
+impl Entry {
+    /// Entry of `T`, registered by `#[derive(Schema)]` under its type's name.
+    pub const fn new<T: Schema + Serialize + DeserializeOwned + Any>(name: &'static str, module: &'static str) -> Entry {
+        Entry {
+            name,
+            version: T::VERSION,
+            module,
+            json_schema: T::json_schema,
+            decode: decode::<T>,
+            #[cfg(feature = "fake")]
+            fake: fake::<T>,
+        }
+    }
+
+    pub fn json_schema(&self) -> Value {
+        (self.json_schema)()
+    }
+
+    /// Decodes and validates `json` as a value of this schema.
+    #[allow(clippy::result_large_err)]
+    pub fn decode(&self, json: &str) -> Result<Box<dyn DynSchema>, DecodeError> {
+        (self.decode)(json)
+    }
+
+    /// A random value of this schema, see [`Schema::fake`].
+    #[cfg(feature = "fake")]
+    pub fn fake(&self, rng: &mut dyn rand::RngCore) -> Box<dyn DynSchema> {
+        (self.fake)(rng)
+    }
+}
+
+inventory::collect!(Entry);
+
+/// Error of a lookup in the registry.
+#[derive(Debug)]
+pub enum RegistryError {
+    /// No schema has this name, or this version.
+    Unknown { name: String, version: Option<u32> },
+    Decode(DecodeError),
+    Encode(JsonError),
+}
+
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::Unknown { name, version: Some(version) } => write!(f, "unknown schema {} v{}", name, version),
+            RegistryError::Unknown { name, version: None } => write!(f, "unknown schema {}", name),
+            RegistryError::Decode(error) => write!(f, "{}", error),
+            RegistryError::Encode(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Every type deriving `Schema` without generics (so every `schema!` type) in the program,
+/// for code choosing schemas at runtime.
+pub struct Registry {
+    entries: Vec<&'static Entry>, // Sorted by name, then version
+}
+
+impl Registry {
+    /// Opens the registry, collecting the registered schemas on the first call.
+    ///
+    /// # Panics
+    ///
+    /// When two schemas are registered under the same name and version, e.g. types of the same name in two
+    /// modules, as lookups couldn't tell them apart.
+    pub fn open() -> &'static Registry {
+        REGISTRY.get_or_init(|| Registry::new(inventory::iter::<Entry>.into_iter().collect()))
+    }
+
+    fn new(mut entries: Vec<&'static Entry>) -> Registry {
+        entries.sort_by_key(|entry| (entry.name, entry.version));
+
+        if let Some([first, second]) = entries.windows(2).find(|pair| (pair[0].name, pair[0].version) == (pair[1].name, pair[1].version)) {
+            panic!("Schemas {}::{} and {}::{} are both registered as {} v{}: rename one of them",
+                   first.module, first.name, second.module, second.name, first.name, first.version);
+        }
+
+        Registry { entries }
+    }
+
+    /// Every schema, sorted by name, then version.
+    pub fn schemas(&self) -> &[&'static Entry] {
+        &self.entries
+    }
+
+    /// Latest version of the schema `name`.
+    pub fn get(&self, name: &str) -> Option<&'static Entry> {
+        self.entries.iter().rev().find(|entry| entry.name == name).copied()
+    }
+
+    pub fn get_version(&self, name: &str, version: u32) -> Option<&'static Entry> {
+        self.entries.iter().find(|entry| entry.name == name && entry.version == version).copied()
+    }
+
+    /// Decodes and validates `json` as a value of the latest version of the schema `name`.
+    #[allow(clippy::result_large_err)]
+    pub fn decode(&self, name: &str, json: &str) -> Result<Box<dyn DynSchema>, RegistryError> {
+        let entry = self.get(name).ok_or_else(|| RegistryError::Unknown { name: name.to_string(), version: None })?;
+        entry.decode(json).map_err(RegistryError::Decode)
+    }
+
+    /// Decodes and validates `json` as a value of the schema `name`, in its `version`.
+    #[allow(clippy::result_large_err)]
+    pub fn decode_version(&self, name: &str, version: u32, json: &str) -> Result<Box<dyn DynSchema>, RegistryError> {
+        let entry = self.get_version(name, version)
+            .ok_or_else(|| RegistryError::Unknown { name: name.to_string(), version: Some(version) })?;
+        entry.decode(json).map_err(RegistryError::Decode)
+    }
+
+    /// Decodes and validates `json` like [`decode`](Registry::decode), and gives it back as a dynamic JSON value,
+    /// with its defaults filled in and unknown fields dropped.
+    #[allow(clippy::result_large_err)]
+    pub fn decode_value(&self, name: &str, json: &str) -> Result<Value, RegistryError> {
+        self.decode(name, json)?.dyn_to_value().map_err(RegistryError::Encode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    mod v1 {
+        crate::schema!(
+            Question {
+                text: String,
+            }
+        );
+    }
+
+    mod v2 {
+        crate::schema!(
+            #[version = 2]
+            Question {
+                text: String where len(1..),
+                top_k: u32 = 5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_schemas() {
+        let registry = Registry::open();
+        let versions: Vec<u32> = registry.schemas().iter()
+            .filter(|entry| entry.name == "Question")
+            .map(|entry| entry.version)
+            .collect();
+
+        assert_eq!(versions, vec![1, 2]);
+        assert_eq!(registry.get("Question").unwrap().module, module_path!().to_string() + "::v2");
+        assert_eq!(registry.get("Question").unwrap().json_schema()["properties"]["top_k"]["default"], 5);
+        assert!(registry.get("TestStruct").is_some());
+        assert!(registry.get_version("Question", 3).is_none());
+    }
+
+    #[test]
+    fn test_decode() {
+        let registry = Registry::open();
+
+        let question = registry.decode("Question", r#"{"$v":2,"text":"What is Rust?"}"#).unwrap();
+        assert_eq!(question.downcast_ref::<v2::Question>().unwrap().top_k, 5);
+        assert!(question.downcast_ref::<v1::Question>().is_none());
+        assert_eq!(question.dyn_to_json().unwrap(), r#"{"$v":2,"text":"What is Rust?","top_k":5}"#);
+
+        let question = registry.decode_version("Question", 1, r#"{"text":""}"#).unwrap();
+        assert_eq!(question.downcast_ref::<v1::Question>().unwrap().text, "");
+
+        assert_eq!(registry.decode_value("Question", r#"{"$v":2,"text":"?","extra":1}"#).unwrap(), json!({ "text": "?", "top_k": 5 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "are both registered as Question v1")]
+    fn test_name_collision() {
+        static FIRST: Entry = Entry::new::<v1::Question>("Question", "doctour_ai::schemas::registry::tests::v1");
+        static SECOND: Entry = Entry::new::<v1::Question>("Question", "doctour_ai::schemas::registry::tests::other");
+
+        Registry::new(vec![&FIRST, &SECOND]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let registry = Registry::open();
+
+        assert!(matches!(registry.decode("Question", r#"{"$v":2,"text":""}"#), Err(RegistryError::Decode(DecodeError::Invalid(_)))));
+        assert!(matches!(registry.decode("Answer", "{}"), Err(RegistryError::Unknown { .. })));
+        assert_eq!(registry.decode_version("Question", 3, "{}").err().unwrap().to_string(), "unknown schema Question v3");
+    }
+}
@@ -0,0 +1,462 @@
+use serde_json::Value;
+use surrealdb::{Connection, Surreal};
+
+use super::Schema;
+
+/// An index of a [`Table`].
+#[derive(Debug, Clone, PartialEq)]
+struct Index {
+    name: String,
+    fields: Vec<String>,
+    unique: bool,
+}
+
+/// A field definition: where it is, e.g. `tags.*`, its type and its assertion.
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    path: String,
+    flexible: bool,
+    kind: String,
+    assert: Option<String>,
+}
+
+/// A `SCHEMAFULL` SurrealDB table storing the documents of a schema, with a `DEFINE FIELD` per field (nested ones
+/// included) typed from its JSON Schema, and `ASSERT`ing its validation rules.
+///
+/// ```ignore
+/// Profile::surreal_table("profiles").unique_index("profile_email", &["email"]).migrate(&db).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Table {
+    name: String,
+    schema: Value,
+    indexes: Vec<Index>,
+}
+
+/// `name` as a SurrealQL identifier, escaped unless it's only made of letters, digits and `_`.
+fn ident(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        name.to_string()
+    } else {
+        format!("`{}`", name.replace('`', "\\`"))
+    }
+}
+
+/// `name`, an identifier from SurrealDB, unescaped.
+fn unescape(name: &str) -> String {
+    name.trim_matches(['`', '⟨', '⟩']).replace("\\`", "`")
+}
+
+/// The parts of the field path `path`, unescaped: `tags.*`, as defined, and `tags[*]`, as SurrealDB reports it,
+/// are both `["tags", "*"]`.
+fn parts(path: &str) -> Vec<String> {
+    let (mut parts, mut part) = (Vec::new(), String::new());
+    let mut chars = path.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '`' | '⟨' => {
+                let end = if c == '`' { '`' } else { '⟩' };
+                while let Some(c) = chars.next().filter(|c| *c != end) {
+                    part.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
+                }
+            }
+            '.' | '[' | ']' => {
+                if !part.is_empty() {
+                    parts.push(std::mem::take(&mut part));
+                }
+            }
+            c => part.push(c),
+        }
+    }
+    if !part.is_empty() {
+        parts.push(part);
+    }
+
+    parts
+}
+
+/// The field path of `parts`, escaped.
+fn path(parts: &[String]) -> String {
+    parts.iter()
+        .map(|part| if part == "*" { part.clone() } else { ident(part) })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// The schema of what isn't `null` in `schema`, and whether `schema` allows `null` (an `Option`).
+fn non_null(schema: &Value) -> (Value, bool) {
+    if let Some([first, second]) = schema.get("anyOf").and_then(Value::as_array).map(Vec::as_slice) {
+        let null = serde_json::json!({ "type": "null" });
+        if *second == null {
+            return (first.clone(), true);
+        }
+    }
+
+    match schema.get("type").and_then(Value::as_array) {
+        Some(kinds) if kinds.iter().any(|kind| kind == "null") => {
+            let kinds: Vec<&Value> = kinds.iter().filter(|kind| *kind != "null").collect();
+            let mut schema = schema.clone();
+            schema["type"] = match kinds[..] {
+                [kind] => kind.clone(),
+                _ => Value::from(kinds.into_iter().cloned().collect::<Vec<_>>()),
+            };
+            (schema, true)
+        }
+        _ => (schema.clone(), false),
+    }
+}
+
+/// SurrealDB type of a JSON value.
+fn value_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(number) if number.is_f64() => "float",
+        Value::Number(_) => "int",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// SurrealDB type of the values of `schema`, `any` when they may have several.
+fn kind(schema: &Value) -> &'static str {
+    let kinds: Vec<&'static str> = if let Some(kind) = schema.get("type").and_then(Value::as_str) {
+        vec![match kind {
+            "boolean" => "bool",
+            "integer" => "int",
+            "number" => "number",
+            "string" => "string",
+            "array" => "array",
+            "object" => "object",
+            "null" => "null",
+            _ => "any",
+        }]
+    } else if let Some(value) = schema.get("const") {
+        vec![value_kind(value)]
+    } else if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        values.iter().map(value_kind).collect()
+    } else if let Some(variants) = schema.get("oneOf").and_then(Value::as_array) {
+        variants.iter().map(kind).collect()
+    } else {
+        vec!["any"]
+    };
+
+    match kinds[..] {
+        [first, ..] if kinds.iter().all(|kind| *kind == first) => first,
+        _ => "any",
+    }
+}
+
+/// `bound` as a SurrealQL number, unless it's an integer outside of SurrealDB's (64-bit) ones, which can't be
+/// out of bounds.
+fn bound(bound: &Value) -> Option<String> {
+    match bound.as_i64() {
+        Some(i64::MIN) | Some(i64::MAX) => None,
+        Some(_) => Some(bound.to_string()),
+        None => bound.as_f64().filter(|_| bound.is_f64()).map(|_| bound.to_string()),
+    }
+}
+
+/// Conditions on `$value` of the validation keywords of `schema`: bounds, lengths, formats and constants.
+fn assertions(schema: &Value) -> Vec<String> {
+    let mut assertions = Vec::new();
+    let keyword = |name: &str| schema.get(name);
+
+    for (name, operator) in [("minimum", ">="), ("exclusiveMinimum", ">"), ("maximum", "<="), ("exclusiveMaximum", "<")] {
+        if let Some(bound) = keyword(name).and_then(bound) {
+            assertions.push(format!("$value {} {}", operator, bound));
+        }
+    }
+
+    for (function, minimum, maximum) in [
+        ("string::len", "minLength", "maxLength"),
+        ("array::len", "minItems", "maxItems"),
+        ("object::len", "minProperties", "maxProperties"),
+    ] {
+        if let Some(length) = keyword(minimum) {
+            assertions.push(format!("{}($value) >= {}", function, length));
+        }
+        if let Some(length) = keyword(maximum) {
+            assertions.push(format!("{}($value) <= {}", function, length));
+        }
+    }
+
+    match keyword("format").and_then(Value::as_str) {
+        Some("email") => assertions.push(String::from("string::is::email($value)")),
+        Some("uri") => assertions.push(String::from("string::is::url($value)")),
+        // Its formats are of date-times
+        Some("date") => assertions.push(String::from(r#"string::is::datetime($value + " 00:00", "%Y-%m-%d %H:%M")"#)),
+        _ => {}
+    }
+
+    if let Some(value) = keyword("const") {
+        assertions.push(format!("$value = {}", value));
+    }
+    if let Some(values) = keyword("enum") {
+        assertions.push(format!("$value INSIDE {}", values));
+    }
+
+    assertions
+}
+
+/// Definitions of the field at `path`, described by `schema`, then of its nested fields. `optional` fields may
+/// be missing (`NONE`), e.g. when they have a default, or their parent is optional.
+fn define(schema: &Value, path: String, optional: bool, fields: &mut Vec<Field>) {
+    let (schema, nullable) = non_null(schema);
+    let base = kind(&schema);
+
+    let kind = match (base, nullable, optional) {
+        ("any", _, _) => String::from("any"),
+        (base, true, _) => format!("option<{} | null>", base),
+        (base, false, true) => format!("option<{}>", base),
+        (base, false, false) => base.to_string(),
+    };
+    let assertions = assertions(&schema);
+    let assert = (!assertions.is_empty()).then(|| {
+        let assertions = assertions.join(" AND ");
+        match (nullable, optional) {
+            (true, _) => format!("$value = NONE OR $value = NULL OR ({})", assertions),
+            (false, true) => format!("$value = NONE OR ({})", assertions),
+            (false, false) => assertions,
+        }
+    });
+
+    // Objects without properties (maps, unions) keep whatever they hold
+    let properties = schema.get("properties").is_some();
+    fields.push(Field { flexible: base == "object" && !properties, path: path.clone(), kind, assert });
+
+    if properties {
+        define_properties(&schema, &path, optional || nullable, fields);
+    } else if let Some(items) = schema.get("items").filter(|_| base == "array") {
+        define(items, format!("{}.*", path), false, fields);
+    }
+}
+
+/// Definitions of the properties of the object described by `schema`, at `path` (empty for documents), by name.
+fn define_properties(schema: &Value, path: &str, optional: bool, fields: &mut Vec<Field>) {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else { return };
+    let required = schema.get("required").and_then(Value::as_array);
+
+    // Sorted, as maps keep the order of their keys with serde_json's `preserve_order`
+    let mut properties: Vec<(&String, &Value)> = properties.iter().collect();
+    properties.sort_by_key(|(name, _)| *name);
+
+    for (name, property) in properties {
+        // The record ID
+        if path.is_empty() && name == "id" {
+            continue;
+        }
+
+        let path = if path.is_empty() { ident(name) } else { format!("{}.{}", path, ident(name)) };
+        let required = required.is_some_and(|required| required.iter().any(|other| other == name));
+        define(property, path, optional || !required, fields);
+    }
+}
+
+impl Table {
+    /// Table `name` storing the documents of `T`. [`Schema::surreal_table`] does the same.
+    pub fn new<T: Schema + ?Sized>(name: &str) -> Table {
+        Table { name: name.to_string(), schema: T::subschema(), indexes: Vec::new() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adds the index `name` on `fields`, which are SurrealQL paths such as `author.name`.
+    pub fn index(mut self, name: &str, fields: &[&str]) -> Table {
+        let fields = fields.iter().map(|field| field.to_string()).collect();
+        self.indexes.push(Index { name: name.to_string(), fields, unique: false });
+        self
+    }
+
+    /// Adds the index `name` on `fields`, like [`index`](Table::index), rejecting duplicates.
+    pub fn unique_index(mut self, name: &str, fields: &[&str]) -> Table {
+        self = self.index(name, fields);
+        if let Some(index) = self.indexes.last_mut() {
+            index.unique = true;
+        }
+        self
+    }
+
+    fn fields(&self) -> Vec<Field> {
+        let mut fields = Vec::new();
+        define_properties(&self.schema, "", false, &mut fields);
+
+        fields
+    }
+
+    /// SurrealQL statements defining the table, its fields and its indexes. Tables of schemas which aren't objects
+    /// (e.g. unions) are `SCHEMALESS`, without fields.
+    pub fn definitions(&self) -> Vec<String> {
+        let table = ident(&self.name);
+        let schemafull = self.schema.get("properties").is_some();
+        let mut definitions = vec![format!("DEFINE TABLE {} {}", table, if schemafull { "SCHEMAFULL" } else { "SCHEMALESS" })];
+
+        for field in self.fields() {
+            let mut definition = format!("DEFINE FIELD {} ON TABLE {}", field.path, table);
+            if field.flexible {
+                definition.push_str(" FLEXIBLE");
+            }
+            definition.push_str(&format!(" TYPE {}", field.kind));
+            if let Some(assert) = field.assert {
+                definition.push_str(&format!(" ASSERT {}", assert));
+            }
+            definitions.push(definition);
+        }
+
+        for index in &self.indexes {
+            definitions.push(format!("DEFINE INDEX {} ON TABLE {} FIELDS {}{}", ident(&index.name), table,
+                                     index.fields.join(", "), if index.unique { " UNIQUE" } else { "" }));
+        }
+
+        definitions
+    }
+
+    /// Applies the definitions to `db`, which replace the previous ones, and removes the fields and indexes
+    /// the table doesn't have anymore. Running it again changes nothing, so it can run on every start.
+    pub async fn migrate<C: Connection>(&self, db: &Surreal<C>) -> Result<(), surrealdb::Error> {
+        db.query(self.definitions().join(";\n")).await?.check()?;
+
+        let info: Option<Value> = db.query(format!("INFO FOR TABLE {}", ident(&self.name))).await?.take(0)?;
+        let defined = |kind: &str| -> Vec<String> {
+            info.as_ref().and_then(|info| info.get(kind)).and_then(Value::as_object)
+                .map(|definitions| definitions.keys().cloned().collect())
+                .unwrap_or_default()
+        };
+
+        // Nested ones included, deepest first
+        let fields: Vec<Vec<String>> = self.fields().iter().map(|field| parts(&field.path)).collect();
+        let mut stale_fields: Vec<Vec<String>> = defined("fields").iter()
+            .map(|name| parts(name))
+            .filter(|name| !fields.contains(name))
+            .collect();
+        stale_fields.sort_by_key(|name| std::cmp::Reverse(name.len()));
+
+        let stale = stale_fields.iter()
+            .map(|name| format!("REMOVE FIELD {} ON TABLE {}", path(name), ident(&self.name)))
+            .chain(defined("indexes").iter()
+                .map(|name| unescape(name))
+                .filter(|name| self.indexes.iter().all(|index| index.name != *name))
+                .map(|name| format!("REMOVE INDEX {} ON TABLE {}", ident(&name), ident(&self.name))))
+            .collect::<Vec<_>>();
+        if !stale.is_empty() {
+            db.query(stale.join(";\n")).await?.check()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::schemas::{JsonSchema, PatchOperation};
+    use super::*;
+
+    crate::schema!(
+        Address {
+            city: String where len(1..),
+            zip: Option<String>,
+        }
+    );
+
+    crate::schema!(
+        #[version = 2]
+        #[migrations(v1_to_v2)]
+        Account {
+            id: String,
+            email: String where email,
+            age: u8 where range(18..),
+            score: f64 where range(0.0..1.0),
+            tags: Vec<String> where len(..=5) each(len(1..=32)),
+            address: Option<Address>,
+            born: Option<String> where date,
+            labels: HashMap<String, String>,
+            active: bool = true,
+            extra: Value,
+        }
+    );
+
+    fn v1_to_v2(document: Value) -> Result<Value, String> {
+        Ok(document)
+    }
+
+    #[test]
+    fn test_definitions() {
+        let definitions = Account::surreal_table("accounts").unique_index("account_email", &["email"]).definitions();
+
+        assert_eq!(definitions, vec![
+            "DEFINE TABLE accounts SCHEMAFULL",
+            "DEFINE FIELD `$v` ON TABLE accounts TYPE option<int> ASSERT $value = NONE OR ($value = 2)",
+            "DEFINE FIELD active ON TABLE accounts TYPE option<bool>",
+            "DEFINE FIELD address ON TABLE accounts TYPE option<object | null>",
+            "DEFINE FIELD address.city ON TABLE accounts TYPE option<string> ASSERT $value = NONE OR (string::len($value) >= 1)",
+            "DEFINE FIELD address.zip ON TABLE accounts TYPE option<string | null>",
+            "DEFINE FIELD age ON TABLE accounts TYPE int ASSERT $value >= 18 AND $value <= 255",
+            "DEFINE FIELD born ON TABLE accounts TYPE option<string | null> ASSERT $value = NONE OR $value = NULL OR \
+             (string::is::datetime($value + \" 00:00\", \"%Y-%m-%d %H:%M\"))",
+            "DEFINE FIELD email ON TABLE accounts TYPE string ASSERT string::is::email($value)",
+            "DEFINE FIELD extra ON TABLE accounts TYPE any",
+            "DEFINE FIELD labels ON TABLE accounts FLEXIBLE TYPE object",
+            "DEFINE FIELD score ON TABLE accounts TYPE number ASSERT $value >= 0.0 AND $value < 1.0",
+            "DEFINE FIELD tags ON TABLE accounts TYPE array ASSERT array::len($value) <= 5",
+            "DEFINE FIELD tags.* ON TABLE accounts TYPE string ASSERT string::len($value) >= 1 AND string::len($value) <= 32",
+            "DEFINE INDEX account_email ON TABLE accounts FIELDS email UNIQUE",
+        ]);
+    }
+
+    #[test]
+    fn test_kinds() {
+        assert_eq!(kind(&PatchOperation::subschema()), "object");
+        assert_eq!(kind(&json!({ "enum": ["a", "b"] })), "string");
+        assert_eq!(kind(&json!({ "enum": ["a", 1] })), "any");
+        assert_eq!(ident("top_k"), "top_k");
+        assert_eq!(unescape("⟨$v⟩"), "$v");
+
+        let definitions = Table::new::<PatchOperation>("operations").definitions();
+        assert_eq!(definitions, vec!["DEFINE TABLE operations SCHEMALESS"]);
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(parts("address.zip"), vec!["address", "zip"]);
+        assert_eq!(parts("tags[*]"), parts("tags.*"));
+        assert_eq!(parts("⟨$v⟩"), vec!["$v"]);
+        assert_eq!(parts("address.`post code`"), vec!["address", "post code"]);
+        assert_eq!(path(&parts("notes[*].`$v`")), "notes.*.`$v`");
+    }
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(()).await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+
+        // Left by a previous version of the schema
+        db.query("DEFINE TABLE accounts SCHEMAFULL;
+                  DEFINE FIELD nickname ON TABLE accounts TYPE option<string>;
+                  DEFINE FIELD address ON TABLE accounts TYPE option<object | null>;
+                  DEFINE FIELD address.street ON TABLE accounts TYPE option<string>;
+                  DEFINE FIELD tags.*.kind ON TABLE accounts TYPE string;
+                  DEFINE INDEX account_nickname ON TABLE accounts FIELDS nickname").await.unwrap().check().unwrap();
+
+        let table = Account::surreal_table("accounts").unique_index("account_email", &["email"]);
+        table.migrate(&db).await.unwrap();
+        table.migrate(&db).await.unwrap();
+
+        let info: Option<Value> = db.query("INFO FOR TABLE accounts").await.unwrap().take(0).unwrap();
+        let info = info.unwrap();
+        let mut fields: Vec<Vec<String>> = info["fields"].as_object().unwrap().keys().map(|name| parts(name)).collect();
+        fields.sort();
+        let mut expected: Vec<Vec<String>> = table.fields().iter().map(|field| parts(&field.path)).collect();
+        expected.sort();
+
+        assert_eq!(fields, expected);
+        assert_eq!(info["indexes"].as_object().unwrap().keys().collect::<Vec<_>>(), vec!["account_email"]);
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::process::ExitCode;
+
+use serde_json::Value;
+
+use super::Registry;
+
+/// First line of the generated declarations.
+pub const HEADER: &str = "// Generated from the schema! types by `schemas typescript`. Don't edit it.";
+
+/// `name` as a property name, quoted unless it's an identifier.
+fn key(name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+
+    if identifier { name.to_string() } else { Value::from(name).to_string() }
+}
+
+/// Names of the declared schemas, and the `$anchor`s of the recursive ones, which their `$ref`s refer to.
+#[derive(Default)]
+struct Declared<'a> {
+    names: BTreeSet<&'a str>,
+    anchors: BTreeMap<&'a str, &'a str>,
+}
+
+impl<'a> FromIterator<&'a str> for Declared<'a> {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(names: I) -> Self {
+        Declared { names: names.into_iter().collect(), anchors: BTreeMap::new() }
+    }
+}
+
+/// `types` as a union, without duplicates.
+fn union(types: impl IntoIterator<Item = String>) -> String {
+    let mut union: Vec<String> = Vec::new();
+    for ty in types {
+        if !union.contains(&ty) {
+            union.push(ty);
+        }
+    }
+
+    union.join(" | ")
+}
+
+/// TypeScript type of the values of `schema`, whose nested objects named after another declaration (in
+/// `declared`) refer to it. `indent` is the depth of the type, for the members of its objects.
+fn type_of(schema: &Value, declared: &Declared, indent: usize, root: bool) -> String {
+    let nested = |schema: &Value| type_of(schema, declared, indent, false);
+
+    // Recursive types, within their own schema
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = declared.anchors.get(reference.trim_start_matches('#'));
+        return name.map(|name| name.to_string()).unwrap_or_else(|| String::from("unknown"));
+    }
+    // Nullable ones are split below, into their name and `null`
+    let nullable = matches!(schema.get("type"), Some(Value::Array(_)));
+    if let Some(title) = schema.get("title").and_then(Value::as_str).filter(|title| !root && !nullable && declared.names.contains(title)) {
+        return title.to_string();
+    }
+    if let Some(value) = schema.get("const") {
+        return value.to_string();
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return union(values.iter().map(Value::to_string));
+    }
+    if let Some(variants) = schema.get("oneOf").or_else(|| schema.get("anyOf")).and_then(Value::as_array) {
+        return union(variants.iter().map(nested));
+    }
+
+    match schema.get("type") {
+        // e.g. `Option`s, `["string", "null"]`
+        Some(Value::Array(kinds)) => union(kinds.iter().map(|kind| {
+            if kind == "null" {
+                return String::from("null");
+            }
+            let mut schema = schema.clone();
+            schema["type"] = kind.clone();
+            type_of(&schema, declared, indent, root)
+        })),
+        Some(Value::String(kind)) => match kind.as_str() {
+            "string" => String::from("string"),
+            "integer" | "number" => String::from("number"),
+            "boolean" => String::from("boolean"),
+            "null" => String::from("null"),
+            "array" => {
+                let items = schema.get("items").map(nested).unwrap_or_else(|| String::from("unknown"));
+                if items.contains(' ') { format!("({})[]", items) } else { format!("{}[]", items) }
+            }
+            "object" => object(schema, declared, indent),
+            _ => String::from("unknown"),
+        },
+        _ => String::from("unknown"),
+    }
+}
+
+/// TypeScript type of an object: its members, sorted by name, or a `Record` for maps. Members that are
+/// serialized but never read are `readonly`.
+fn object(schema: &Value, declared: &Declared, indent: usize) -> String {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        let values = schema.get("additionalProperties").filter(|values| values.is_object())
+            .map(|values| type_of(values, declared, indent, false))
+            .unwrap_or_else(|| String::from("unknown"));
+        return format!("Record<string, {}>", values);
+    };
+    let required = schema.get("required").and_then(Value::as_array);
+
+    // Sorted, as maps keep the order of their keys with serde_json's `preserve_order`
+    let mut properties: Vec<(&String, &Value)> = properties.iter().collect();
+    properties.sort_by_key(|(name, _)| *name);
+
+    let mut members = String::from("{\n");
+    for (name, property) in properties {
+        // `to_json` always writes the version, which only incoming documents may leave out
+        let optional = name != "$v" && !required.is_some_and(|required| required.iter().any(|other| other == name));
+        let read_only = property.get("readOnly").is_some_and(|read_only| read_only == true);
+        members.push_str(&format!("{}{}{}{}: {};\n", "    ".repeat(indent + 1), if read_only { "readonly " } else { "" },
+                                  key(name), if optional { "?" } else { "" }, type_of(property, declared, indent + 1, false)));
+    }
+    members.push_str(&"    ".repeat(indent));
+    members.push('}');
+
+    members
+}
+
+/// Declaration of the type `name`, from its JSON Schema: an interface for objects, an alias otherwise.
+fn declaration(name: &str, schema: &Value, declared: &Declared) -> String {
+    let ty = type_of(schema, declared, 0, true);
+
+    if schema.get("properties").is_some() && schema.get("type").is_some_and(|kind| kind == "object") {
+        format!("export interface {} {}\n", name, ty)
+    } else {
+        format!("export type {} = {};\n", name, ty)
+    }
+}
+
+/// TypeScript declarations (a `.d.ts`) of the latest version of every schema of `registry`, with their JSON
+/// names, optional members for optional and defaulted fields, unions for enums, and nested schemas referred
+/// to by name.
+pub fn declarations(registry: &Registry) -> String {
+    let latest: Vec<_> = registry.schemas().iter().copied()
+        .filter(|entry| registry.get(entry.name).is_some_and(|latest| std::ptr::eq(latest, *entry)))
+        .collect();
+    let schemas: Vec<(&str, Value)> = latest.iter().map(|entry| (entry.name, entry.json_schema())).collect();
+
+    let mut declared: Declared = schemas.iter().map(|(name, _)| *name).collect();
+    for (name, schema) in &schemas {
+        if let Some(anchor) = schema.get("$anchor").and_then(Value::as_str) {
+            declared.anchors.insert(anchor, name);
+        }
+    }
+
+    let mut declarations = format!("{}\n", HEADER);
+    for (name, schema) in &schemas {
+        declarations.push('\n');
+        declarations.push_str(&declaration(name, schema, &declared));
+    }
+
+    declarations
+}
+
+/// The `schemas` command, with the arguments after its name, exporting the schemas of `registry`:
+///
+/// * `schemas typescript [path]` - Writes the TypeScript declarations of the schemas to `path` (a `.d.ts`),
+///   or prints them.
+/// * `schemas typescript --check <path>` - Fails when `path` isn't up to date, so the frontend can't drift
+///   from the Rust definitions.
+///
+/// The registry only holds the schemas of the crates linked into the binary: a crate declaring schemas of
+/// its own calls it from its own `src/bin/schemas.rs`, to export them along with those of `doctour_ai`.
+pub fn command(args: &[&str], registry: &Registry) -> ExitCode {
+    match args {
+        ["typescript"] => {
+            print!("{}", declarations(registry));
+            ExitCode::SUCCESS
+        }
+        ["typescript", "--check", path] => match std::fs::read_to_string(path) {
+            Ok(existing) if existing == declarations(registry) => {
+                println!("{} is up to date", path);
+                ExitCode::SUCCESS
+            }
+            Ok(_) => {
+                eprintln!("{} is outdated, run `schemas typescript {}`", path, path);
+                ExitCode::FAILURE
+            }
+            Err(error) => {
+                eprintln!("Could not read {}: {}", path, error);
+                ExitCode::FAILURE
+            }
+        },
+        ["typescript", path] if !path.starts_with("--") => match std::fs::write(path, declarations(registry)) {
+            Ok(()) => {
+                println!("Wrote the TypeScript declarations to {}", path);
+                ExitCode::SUCCESS
+            }
+            Err(error) => {
+                eprintln!("Could not write {}: {}", path, error);
+                ExitCode::FAILURE
+            }
+        },
+        _ => {
+            eprintln!("Usage: schemas typescript [path] | schemas typescript --check <path>");
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::schemas::JsonSchema;
+    use super::*;
+
+    crate::schema!(
+        #[rename_all = "lowercase"]
+        #[sample = Plan::Pro]
+        enum Plan {
+            Free,
+            Pro,
+        }
+    );
+
+    crate::schema!(
+        Shipping {
+            street: String,
+            zip: Option<String>,
+        }
+    );
+
+    crate::schema!(
+        #[rename_all = "camelCase"]
+        Customer {
+            full_name: String,
+            plan: Plan = Plan::Free,
+            next_plan: Option<Plan>,
+            shipping: Option<Shipping>,
+            addresses: Vec<Shipping>,
+            notes: Vec<Option<String>>,
+            attributes: std::collections::HashMap<String, u32>,
+            user_id: u64 as "user-id",
+            active: bool = true,
+        }
+    );
+
+    #[test]
+    fn test_declaration() {
+        let declared: Declared = ["Customer", "Plan", "Shipping"].into_iter().collect();
+
+        assert_eq!(declaration("Customer", &Customer::json_schema(), &declared), [
+            "export interface Customer {",
+            "    active?: boolean;",
+            "    addresses: Shipping[];",
+            "    attributes: Record<string, number>;",
+            "    fullName: string;",
+            "    nextPlan?: Plan | null;",
+            "    notes: (string | null)[];",
+            "    plan?: Plan;",
+            "    shipping?: Shipping | null;",
+            "    \"user-id\": number;",
+            "}\n",
+        ].join("\n"));
+        assert_eq!(declaration("Plan", &Plan::json_schema(), &declared), "export type Plan = \"free\" | \"pro\";\n");
+    }
+
+    #[test]
+    fn test_nested_types() {
+        let shipping = type_of(&Option::<Shipping>::subschema(), &Declared::default(), 0, false);
+        assert_eq!(shipping, "{\n    street: string;\n    zip?: string | null;\n} | null");
+
+        assert_eq!(type_of(&json!({}), &Declared::default(), 0, false), "unknown");
+        assert_eq!(key("$v"), "$v");
+    }
+
+    #[test]
+    fn test_declarations() {
+        let declarations = declarations(Registry::open());
+
+        assert!(declarations.starts_with(HEADER));
+        assert!(declarations.contains("export interface Customer {\n"));
+        assert!(declarations.contains("export interface Question {\n    $v: 2;\n"));
+        assert_eq!(declarations.matches("export interface Question ").count(), 1);
+        assert!(declarations.contains("export interface Section {\n    next?: Section | null;\n    subsections: Section[];\n    title: string;\n    readonly words?: number;\n}\n"));
+    }
+
+    #[test]
+    fn test_command() {
+        let path = std::env::temp_dir().join(format!("doctour_ai_schemas_{}.d.ts", std::process::id()));
+        let path = path.to_str().unwrap();
+        let registry = Registry::open();
+
+        assert_eq!(command(&["typescript", "--check", path], registry), ExitCode::FAILURE);
+        assert_eq!(command(&["typescript", path], registry), ExitCode::SUCCESS);
+        assert_eq!(command(&["typescript", "--check", path], registry), ExitCode::SUCCESS);
+
+        std::fs::write(path, format!("{}\nexport type Stale = string;\n", HEADER)).unwrap();
+        assert_eq!(command(&["typescript", "--check", path], registry), ExitCode::FAILURE);
+        assert_eq!(command(&["typescript", "--check"], registry), ExitCode::from(2));
+        assert_eq!(command(&[], registry), ExitCode::from(2));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
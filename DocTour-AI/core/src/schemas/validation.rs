@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
+/// A value breaking a validation rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// JSON pointer of the value, e.g. `/tags/2`. Empty for the whole document.
+    pub path: String,
+    /// Name of the rule, e.g. `range`.
+    pub rule: String,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(path: &str, rule: &str, message: impl Into<String>) -> Violation {
+        Violation { path: path.to_string(), rule: rule.to_string(), message: message.into() }
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", if self.path.is_empty() { "/" } else { &self.path }, self.message)
+    }
+}
+
+/// JSON pointer of `token` inside the value at `path`.
+pub fn pointer(path: &str, token: impl Display) -> String {
+    format!("{}/{}", path, token.to_string().replace('~', "~0").replace('/', "~1"))
+}
+
+/// `Validate` is a trait that checks a value against the rules declared with `where` in `schema!`
+/// (or `#[validate(...)]` with the derive), including the rules of nested schemas.
+pub trait Validate {
+    /// Adds every rule broken by this value, found at the JSON pointer `path`, to `violations`.
+    fn validate_at(&self, path: &str, violations: &mut Vec<Violation>);
+
+    /// Checks every rule, and returns every violation rather than the first one.
+    fn validate(&self) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        self.validate_at("", &mut violations);
+
+        if violations.is_empty() { Ok(()) } else { Err(violations) }
+    }
+}
+
+/// Implements `Validate` for types without rules of their own.
+macro_rules! always_valid {
+    ($($type:ty),+) => {
+        $(
+            impl Validate for $type {
+                fn validate_at(&self, _path: &str, _violations: &mut Vec<Violation>) {}
+            }
+        )+
+    };
+}
+
+always_valid!(bool, char, String, str, f32, f64, (), serde_json::Value);
+always_valid!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl<T: Validate + ?Sized> Validate for &T {
+    fn validate_at(&self, path: &str, violations: &mut Vec<Violation>) {
+        (**self).validate_at(path, violations);
+    }
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate_at(&self, path: &str, violations: &mut Vec<Violation>) {
+        if let Some(value) = self {
+            value.validate_at(path, violations);
+        }
+    }
+}
+
+impl<T: Validate> Validate for Box<T> {
+    fn validate_at(&self, path: &str, violations: &mut Vec<Violation>) {
+        (**self).validate_at(path, violations);
+    }
+}
+
+impl<T: Validate> Validate for [T] {
+    fn validate_at(&self, path: &str, violations: &mut Vec<Violation>) {
+        for (index, value) in self.iter().enumerate() {
+            value.validate_at(&pointer(path, index), violations);
+        }
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate_at(&self, path: &str, violations: &mut Vec<Violation>) {
+        self.as_slice().validate_at(path, violations);
+    }
+}
+
+impl<T: Validate, S> Validate for HashMap<String, T, S> {
+    fn validate_at(&self, path: &str, violations: &mut Vec<Violation>) {
+        for (key, value) in self {
+            value.validate_at(&pointer(path, key), violations);
+        }
+    }
+}
+
+impl<T: Validate> Validate for BTreeMap<String, T> {
+    fn validate_at(&self, path: &str, violations: &mut Vec<Violation>) {
+        for (key, value) in self {
+            value.validate_at(&pointer(path, key), violations);
+        }
+    }
+}
+
+/// Wraps a field so it is validated only when its type implements [`Validate`], which types of other
+/// crates may not: `(&Nested(&field)).validate_nested(..)` picks [`NestedValidate`] then, and [`NotValidated`] otherwise.
+pub struct Nested<'a, T>(pub &'a T);
+
+pub trait NestedValidate {
+    fn validate_nested(&self, path: &str, violations: &mut Vec<Violation>);
+}
+
+impl<T: Validate> NestedValidate for Nested<'_, T> {
+    fn validate_nested(&self, path: &str, violations: &mut Vec<Violation>) {
+        self.0.validate_at(path, violations);
+    }
+}
+
+pub trait NotValidated {
+    fn validate_nested(&self, path: &str, violations: &mut Vec<Violation>);
+}
+
+impl<T> NotValidated for &Nested<'_, T> {
+    fn validate_nested(&self, _path: &str, _violations: &mut Vec<Violation>) {}
+}
+
+/// Length checked by the `len` rule: characters of a string, items of a collection.
+pub trait Length {
+    fn length(&self) -> usize;
+}
+
+impl Length for str {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl Length for String {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl<T> Length for [T] {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> Length for Vec<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, S> Length for HashSet<T, S> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> Length for BTreeSet<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V, S> Length for HashMap<K, V, S> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V> Length for BTreeMap<K, V> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Keywords bounding what the `len` rule counts in a JSON Schema: items of arrays, properties of objects,
+/// characters of strings.
+pub fn length_keywords(schema: &Value) -> (&'static str, &'static str) {
+    let is = |kind: &str| match schema.get("type") {
+        Some(Value::String(other)) => other == kind,
+        Some(Value::Array(kinds)) => kinds.iter().any(|other| other == kind),
+        _ => false,
+    };
+
+    if is("array") {
+        ("minItems", "maxItems")
+    } else if is("object") {
+        ("minProperties", "maxProperties")
+    } else {
+        ("minLength", "maxLength")
+    }
+}
+
+/// Checks of the string rules.
+pub mod rules {
+    /// `local@domain.tld`, without spaces and with a single `@`.
+    pub fn email(value: &str) -> bool {
+        let Some((local, domain)) = value.split_once('@') else { return false };
+
+        !local.is_empty()
+            && !domain.contains('@')
+            && !value.chars().any(char::is_whitespace)
+            && domain.split('.').count() >= 2
+            && domain.split('.').all(|label| !label.is_empty())
+    }
+
+    /// Absolute `http` or `https` URL with a host.
+    pub fn url(value: &str) -> bool {
+        let Some((scheme, rest)) = value.split_once("://") else { return false };
+        let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+
+        matches!(scheme, "http" | "https")
+            && !host.is_empty()
+            && !value.chars().any(char::is_whitespace)
+    }
+
+    /// Calendar date as `YYYY-MM-DD` (RFC 3339 `full-date`).
+    pub fn date(value: &str) -> bool {
+        let parts: Vec<&str> = value.split('-').collect();
+        let [year, month, day] = parts[..] else { return false };
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return false;
+        }
+
+        let (Ok(year), Ok(month), Ok(day)) = (year.parse::<u32>(), month.parse::<u32>(), day.parse::<u32>()) else {
+            return false;
+        };
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return false,
+        };
+
+        (1..=days).contains(&day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules() {
+        assert!(rules::email("ana@doctour.ai"));
+        assert!(!rules::email("ana@doctour"));
+        assert!(!rules::email("ana doctour@ai.com"));
+        assert!(rules::url("https://doctour.ai/docs?page=1"));
+        assert!(!rules::url("ftp://doctour.ai"));
+        assert!(rules::date("2024-02-29"));
+        assert!(!rules::date("2023-02-29"));
+        assert!(!rules::date("2024-2-1"));
+    }
+
+    #[test]
+    fn test_pointer() {
+        assert_eq!(pointer("", "tags"), "/tags");
+        assert_eq!(pointer("/tags", 2), "/tags/2");
+        assert_eq!(pointer("", "a/b~c"), "/a~1b~0c");
+    }
+}
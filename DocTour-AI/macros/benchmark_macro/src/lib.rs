@@ -3,17 +3,62 @@ extern crate quote;
 extern crate syn;
 
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use syn::{parse_macro_input, Block, FnArg, Ident, ItemFn, LitInt, LitStr, Pat, ReturnType, Type};
+
+/// Arguments of the statistical mode: `#[benchmark(iters = 1000, warmup = 50)]`.
+#[derive(Default)]
+struct Args {
+    iters: Option<LitInt>,
+    warmup: Option<LitInt>,
+}
+
+/// Type of the function's result, and its body made runnable inside a closure or an async block.
+///
+/// The result's type must be known before it is returned, so it can be inspected. `impl Trait` can't annotate
+/// a binding, so those are left to inference (`_`).
+fn wrap_body(output: &ReturnType, block: &Block) -> (TokenStream2, TokenStream2) {
+    let ty = match output {
+        ReturnType::Default => Some(quote!(())),
+        ReturnType::Type(_, ty) if matches!(**ty, Type::ImplTrait(_)) => None,
+        ReturnType::Type(_, ty) => Some(quote!(#ty)),
+    };
+
+    // The body runs inside a closure (or an async block), so `return` and `?` would target it instead of
+    // the function. A `return` that never runs pins its type to the function's one, so both keep working.
+    let fake_return = ty.as_ref().map(|ty| quote! {
+        #[allow(unreachable_code, clippy::diverging_sub_expression)]
+        if false {
+            let _fake_return: #ty = loop {};
+            return _fake_return;
+        }
+    });
+    let body = quote!({ #fake_return #block });
+
+    (ty.unwrap_or(quote!(_)), body)
+}
 
 /// The `benchmark` macro is a procedural macro that measures the execution time of a function.
 ///
 /// This macro is used as an attribute on a function. When the function is called, it records the current time,
-/// executes the function, then prints the elapsed time to the console.
+/// executes the function, then reports the elapsed time through `system::benchmark::report`: a structured
+/// `Logger` line and an observation in the `benchmark_duration_seconds` histogram of the metrics registry.
+/// The function is named after its module path, e.g. `core::rag::search`. While the
+/// `system::benchmark::profiler::Profiler` runs, every call is also a span of its call tree.
+///
+/// With `iters` (and optionally `warmup`), the body runs that many times on each call and the distribution of
+/// its run times is reported instead, through `system::benchmark::measure` (or `measure_async`). Each run gets
+/// its own clone of the arguments, which must then implement `Clone` (references do), and the value of the last
+/// run is returned. Side effects of the body happen on every run, and methods can't take `self` by value.
+///
+/// `async fn`s are timed until their body completes, not until their future is built. Functions returning a
+/// `Result` are reported with the `ok` or `error` status, even when they bail out early with `?` or `return`.
+/// Methods taking `self` in any form are supported.
 ///
 /// # Arguments
 ///
-/// * `_attr: TokenStream` - The attributes applied to the macro. This is currently unused.
+/// * `attr: TokenStream` - Either empty or `iters = <n>, warmup = <n>`.
 /// * `item: TokenStream` - The function to be benchmarked.
 ///
 /// # Returns
@@ -27,19 +72,235 @@ use syn::{parse_macro_input, ItemFn};
 /// fn my_function() {
 ///     // Some code...
 /// }
+///
+/// #[benchmark(iters = 1000, warmup = 50)]
+/// fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+///     // Some code...
+/// }
+///
+/// #[benchmark]
+/// async fn retrieve(&self, question: &str) -> Result<Vec<Document>, DBError> {
+///     // Some code...
+/// }
 /// ```
 #[proc_macro_attribute]
-pub fn benchmark(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn benchmark(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = Args::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("iters") {
+            args.iters = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("warmup") {
+            args.warmup = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `iters` or `warmup`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+
     let input_fn = parse_macro_input!(item as ItemFn);
 
     let ItemFn { attrs, vis, sig, block } = input_fn;
 
+    let name = &sig.ident;
+    let is_async = sig.asyncness.is_some();
+
+    let (ty, body) = wrap_body(&sig.output, &block);
+
+    let output = match args {
+        Args { iters: None, warmup: Some(warmup) } => {
+            return syn::Error::new(warmup.span(), "`warmup` requires `iters`")
+                .to_compile_error()
+                .into();
+        }
+        Args { iters: Some(iters), warmup } => {
+            let warmup = warmup.map(|warmup| quote!(#warmup)).unwrap_or(quote!(0));
+
+            // Every run consumes its own clone of the arguments
+            let clones = sig.inputs.iter()
+                .filter_map(|input| match input {
+                    FnArg::Typed(typed) => match &*typed.pat {
+                        Pat::Ident(pat) => Some(&pat.ident),
+                        _ => None,
+                    },
+                    FnArg::Receiver(_) => None,
+                })
+                .map(|argument| quote!(let #argument = ::std::clone::Clone::clone(&#argument);));
+            let clones = quote!(#(#clones)*);
+
+            let (enter, measure) = if is_async {
+                (quote!(new), quote! {
+                    _span.instrument(::system::benchmark::measure_async(_name, #iters, #warmup, move || {
+                        #clones
+                        async move #body
+                    }, |_value| (&::system::benchmark::Probe(_value)).outcome())).await
+                })
+            } else {
+                (quote!(enter), quote! {
+                    ::system::benchmark::measure(_name, #iters, #warmup, move || {
+                        #clones
+                        #body
+                    }, |_value| (&::system::benchmark::Probe(_value)).outcome())
+                })
+            };
+
+            quote! {
+                #(#attrs)* #vis #sig {
+                    #[allow(unused_imports)]
+                    use ::system::benchmark::{AnyOutcome, ResultOutcome};
+
+                    let _name = concat!(module_path!(), "::", stringify!(#name));
+                    let _span = ::system::benchmark::profiler::Span::#enter(_name);
+                    let (_result, _stats): (#ty, _) = #measure;
+                    drop(_span);
+                    ::system::benchmark::report_stats(&_stats);
+                    _result
+                }
+            }
+        }
+        Args { iters: None, warmup: None } => {
+            // Futures only make their span current while polled, as they may be polled in turn or on other threads
+            let (enter, run) = if is_async {
+                (quote!(new), quote!(_span.instrument(async move #body).await))
+            } else {
+                (quote!(enter), quote!((move || #body)()))
+            };
+
+            quote! {
+                #(#attrs)* #vis #sig {
+                    #[allow(unused_imports)]
+                    use ::system::benchmark::{AnyOutcome, ResultOutcome};
+
+                    let _name = concat!(module_path!(), "::", stringify!(#name));
+                    let _span = ::system::benchmark::profiler::Span::#enter(_name);
+                    let _tracker = ::system::memory::Tracker::start();
+                    let _instant = std::time::Instant::now();
+                    let _result: #ty = #run;
+                    let _elapsed = _instant.elapsed();
+                    drop(_span);
+                    let _usage = _tracker.finish();
+                    ::system::benchmark::report(_name, _elapsed, (&::system::benchmark::Probe(&_result)).outcome(), _usage);
+                    _result
+                }
+            }
+        }
+    };
+
+    TokenStream::from(output)
+}
+
+/// Arguments of `#[traced(skip(password), level = "debug")]`.
+#[derive(Default)]
+struct TraceArgs {
+    skip: Vec<Ident>,
+    level: Option<LitStr>,
+}
+
+/// The `traced` macro is a procedural macro that logs the calls of a function through `system::Logger`.
+///
+/// This macro is used as an attribute on a function. Each call logs an `enter <fn>` line with the value of every
+/// argument as a field, then an `exit <fn>` line with the call's duration. When the function returns an `Err`,
+/// the exit line is logged at the error level instead, with the error as a field. The target of both lines is
+/// the function's module path.
+///
+/// Arguments are logged with their `Debug` form, or `_` for types that don't implement it. `skip(...)` leaves
+/// arguments out, e.g. secrets or large buffers, and `self` is never logged. `level` is the level of the enter
+/// and exit lines, one of `trace`, `debug`, `info` (the default), `warn` or `error`.
+///
+/// Like `#[benchmark]`, it supports `async fn`s, methods, and early returns with `?` or `return`.
+///
+/// # Arguments
+///
+/// * `attr: TokenStream` - Either empty or `skip(<argument>, ...), level = "<level>"`.
+/// * `item: TokenStream` - The function to be traced.
+///
+/// # Returns
+///
+/// * `TokenStream` - The generated code, which includes the logging logic.
+///
+/// # Example
+///
+/// ```rust, ignore
+/// #[traced(skip(password), level = "debug")]
+/// fn login(user: &str, password: &str) -> Result<Session, AuthError> {
+///     // Some code...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn traced(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = TraceArgs::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("skip") {
+            meta.parse_nested_meta(|argument| {
+                args.skip.push(argument.path.require_ident()?.clone());
+                Ok(())
+            })
+        } else if meta.path.is_ident("level") {
+            args.level = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `skip` or `level`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+
+    let level = match args.level.as_ref().map(LitStr::value).as_deref() {
+        None | Some("info") => quote!(::system::Level::Info),
+        Some("trace") => quote!(::system::Level::Trace),
+        Some("debug") => quote!(::system::Level::Debug),
+        Some("warn") => quote!(::system::Level::Warn),
+        Some("error") => quote!(::system::Level::Error),
+        Some(_) => {
+            return syn::Error::new(args.level.unwrap().span(), "expected `trace`, `debug`, `info`, `warn` or `error`")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let input_fn = parse_macro_input!(item as ItemFn);
+
+    let ItemFn { attrs, vis, sig, block } = input_fn;
+
+    let arguments: Vec<&Ident> = sig.inputs.iter()
+        .filter_map(|input| match input {
+            FnArg::Typed(typed) => match &*typed.pat {
+                Pat::Ident(pat) => Some(&pat.ident),
+                _ => None, // Destructured arguments have no single name to log
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect();
+
+    if let Some(unknown) = args.skip.iter().find(|skip| !arguments.contains(skip)) {
+        return syn::Error::new(unknown.span(), format!("`{}` is not an argument of this function", unknown))
+            .to_compile_error()
+            .into();
+    }
+
+    let fields = arguments.iter()
+        .filter(|argument| !args.skip.contains(argument))
+        .map(|argument| quote!((stringify!(#argument), (&::system::trace::Value(&#argument)).render())));
+
+    let name = &sig.ident;
+    let (ty, body) = wrap_body(&sig.output, &block);
+
+    let run = if sig.asyncness.is_some() {
+        quote!(async move #body.await)
+    } else {
+        quote!((move || #body)())
+    };
+
     let output = quote! {
         #(#attrs)* #vis #sig {
+            #[allow(unused_imports)]
+            use ::system::trace::{AnyError, DebugValue, OpaqueError, OpaqueValue, ResultError};
+
+            let _name = stringify!(#name);
+            ::system::trace::enter(module_path!(), _name, #level, &[#(#fields),*]);
             let _instant = std::time::Instant::now();
-            let _result = (|| #block )();
-            let benchmark_msg = format!("BENCHMARK<{}> = {:?}ms", stringify!(#sig), _instant.elapsed().as_millis());
-            dbg!(benchmark_msg);
+            let _result: #ty = #run;
+            ::system::trace::exit(module_path!(), _name, #level, _instant.elapsed(), (&&&::system::benchmark::Probe(&_result)).error());
             _result
         }
     };
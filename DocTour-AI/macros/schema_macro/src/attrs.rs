@@ -0,0 +1,270 @@
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
+use syn::{Attribute, Expr, ExprPath, Ident, LitBool, LitInt, LitStr, Token};
+
+/// Skips the value of a serde option that doesn't change the schema.
+fn ignore(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|_| Ok(()))?;
+    }
+    Ok(())
+}
+
+/// Calls `parse` with every option of the `#[serde(...)]` attributes.
+fn serde_options(attrs: &[Attribute], mut parse: impl FnMut(&ParseNestedMeta) -> syn::Result<bool>) -> syn::Result<()> {
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if !parse(&meta)? {
+                ignore(&meta)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Case convention of `#[serde(rename_all = "...")]`.
+#[derive(Clone, Copy)]
+pub enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(value: &LitStr) -> syn::Result<RenameRule> {
+        match value.value().as_str() {
+            "lowercase" => Ok(RenameRule::Lower),
+            "UPPERCASE" => Ok(RenameRule::Upper),
+            "PascalCase" => Ok(RenameRule::Pascal),
+            "camelCase" => Ok(RenameRule::Camel),
+            "snake_case" => Ok(RenameRule::Snake),
+            "SCREAMING_SNAKE_CASE" => Ok(RenameRule::ScreamingSnake),
+            "kebab-case" => Ok(RenameRule::Kebab),
+            "SCREAMING-KEBAB-CASE" => Ok(RenameRule::ScreamingKebab),
+            _ => Err(syn::Error::new(value.span(), "unknown rename rule")),
+        }
+    }
+
+    /// Name of a variant (written in PascalCase) under this rule, as serde renames it.
+    pub fn variant(&self, name: &str) -> String {
+        let snake = pascal_to_snake(name);
+
+        match self {
+            RenameRule::Lower => name.to_lowercase(),
+            RenameRule::Upper => name.to_uppercase(),
+            RenameRule::Pascal => name.to_string(),
+            RenameRule::Camel => lower_first(name),
+            RenameRule::Snake => snake,
+            RenameRule::ScreamingSnake => snake.to_uppercase(),
+            RenameRule::Kebab => snake.replace('_', "-"),
+            RenameRule::ScreamingKebab => snake.replace('_', "-").to_uppercase(),
+        }
+    }
+
+    /// Name of a field (written in snake_case) under this rule, as serde renames it.
+    pub fn field(&self, name: &str) -> String {
+        match self {
+            RenameRule::Lower | RenameRule::Snake => name.to_string(),
+            RenameRule::Upper | RenameRule::ScreamingSnake => name.to_uppercase(),
+            RenameRule::Pascal => snake_to_pascal(name),
+            RenameRule::Camel => lower_first(&snake_to_pascal(name)),
+            RenameRule::Kebab => name.replace('_', "-"),
+            RenameRule::ScreamingKebab => name.replace('_', "-").to_uppercase(),
+        }
+    }
+}
+
+fn pascal_to_snake(name: &str) -> String {
+    let mut snake = String::new();
+    for (index, char) in name.char_indices() {
+        if index > 0 && char.is_uppercase() {
+            snake.push('_');
+        }
+        snake.push(char.to_ascii_lowercase());
+    }
+
+    snake
+}
+
+fn snake_to_pascal(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+        })
+        .collect()
+}
+
+fn lower_first(name: &str) -> String {
+    let mut chars = name.chars();
+    chars.next().map(|first| first.to_lowercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// How values of an enum are told apart in JSON.
+pub enum Tagging {
+    /// `{"variant": {...}}`, serde's default.
+    External,
+    /// `{"tag": "variant", ...}`.
+    Internal(String),
+    /// `{"tag": "variant", "content": {...}}`.
+    Adjacent(String, String),
+    /// Only the variant's content.
+    Untagged,
+}
+
+/// How serde sees a struct or an enum, from its `#[serde(...)]` attributes.
+pub struct SerdeContainer {
+    pub rename_all: Option<RenameRule>,
+    pub tagging: Tagging,
+    pub deny_unknown_fields: bool,
+}
+
+impl SerdeContainer {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<SerdeContainer> {
+        let (mut rename_all, mut tag, mut content, mut untagged) = (None, None, None, false);
+        let mut deny_unknown_fields = false;
+
+        serde_options(attrs, |meta| {
+            if meta.path.is_ident("rename_all") {
+                rename_all = Some(RenameRule::parse(&meta.value()?.parse()?)?);
+            } else if meta.path.is_ident("tag") {
+                tag = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("content") {
+                content = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("untagged") {
+                untagged = true;
+            } else if meta.path.is_ident("deny_unknown_fields") {
+                deny_unknown_fields = true;
+            } else {
+                return Ok(false);
+            }
+            Ok(true)
+        })?;
+
+        let tagging = match (tag, content, untagged) {
+            (_, _, true) => Tagging::Untagged,
+            (Some(tag), Some(content), _) => Tagging::Adjacent(tag, content),
+            (Some(tag), None, _) => Tagging::Internal(tag),
+            _ => Tagging::External,
+        };
+
+        Ok(SerdeContainer { rename_all, tagging, deny_unknown_fields })
+    }
+}
+
+/// How serde sees a field or a variant, from its `#[serde(...)]` attributes.
+pub struct SerdeField {
+    pub json_name: String,
+    pub skip: bool,
+    /// `#[serde(skip_deserializing)]`: serialized, but never read.
+    pub read_only: bool,
+    pub default: bool,
+    /// Function of `#[serde(default = "...")]`, returning the default value.
+    pub default_fn: Option<ExprPath>,
+}
+
+impl SerdeField {
+    /// `rename` is how the container renames it when it has no `rename` of its own.
+    pub fn parse(attrs: &[Attribute], ident: &Ident, rename: impl Fn(&str) -> String) -> syn::Result<SerdeField> {
+        let mut serde = SerdeField { json_name: rename(&ident.unraw().to_string()), skip: false, read_only: false, default: false,
+                                     default_fn: None };
+
+        serde_options(attrs, |meta| {
+            if meta.path.is_ident("rename") {
+                serde.json_name = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("skip") {
+                serde.skip = true;
+            } else if meta.path.is_ident("skip_deserializing") {
+                serde.read_only = true;
+            } else if meta.path.is_ident("default") {
+                serde.default = true;
+                if meta.input.peek(Token![=]) {
+                    serde.default_fn = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                }
+            } else {
+                return Ok(false);
+            }
+            Ok(true)
+        })?;
+
+        Ok(serde)
+    }
+}
+
+/// Options of `#[schema(...)]`, for what the derive generates besides the traits.
+pub struct SchemaOptions {
+    /// `#[schema(builder)]`: a `<Name>Builder`.
+    pub builder: bool,
+    /// `#[schema(version = 2)]`: version of the schema, 1 by default, which versioned documents carry.
+    pub version: Option<LitInt>,
+    /// `#[schema(stamp = false)]`: a version for the registry only, which documents don't carry.
+    pub stamp: bool,
+    /// `#[schema(migrations(v1_to_v2, ...))]`: functions upgrading documents of older versions.
+    pub migrations: Vec<syn::Path>,
+    /// `#[schema(round_trip = false)]`: no round-trip test, which types declared in a function can't run.
+    pub round_trip: bool,
+    /// `#[schema(sample = <expr>)]`: value of the round-trip test, besides the random ones of the `fake` feature.
+    pub sample: Option<Expr>,
+}
+
+impl Default for SchemaOptions {
+    fn default() -> Self {
+        SchemaOptions { builder: false, version: None, stamp: true, migrations: Vec::new(), round_trip: true, sample: None }
+    }
+}
+
+impl SchemaOptions {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<SchemaOptions> {
+        let mut options = SchemaOptions::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("schema")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("builder") {
+                    options.builder = true;
+                    Ok(())
+                } else if meta.path.is_ident("version") {
+                    options.version = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("stamp") {
+                    options.stamp = meta.value()?.parse::<LitBool>()?.value;
+                    Ok(())
+                } else if meta.path.is_ident("migrations") {
+                    meta.parse_nested_meta(|migration| {
+                        options.migrations.push(migration.path);
+                        Ok(())
+                    })
+                } else if meta.path.is_ident("round_trip") {
+                    options.round_trip = meta.value()?.parse::<LitBool>()?.value;
+                    Ok(())
+                } else if meta.path.is_ident("sample") {
+                    options.sample = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `builder`, `version`, `stamp`, `migrations`, `round_trip` or `sample`"))
+                }
+            })?;
+        }
+
+        match &options.version {
+            Some(version) if !options.migrations.is_empty() && options.migrations.len() as u32 + 1 != version.base10_parse::<u32>()? => {
+                Err(syn::Error::new(version.span(), "a versioned schema needs a migration per older version"))
+            }
+            Some(_) if !options.stamp && !options.migrations.is_empty() => {
+                Err(syn::Error::new(options.migrations[0].segments[0].ident.span(), "migrations need stamped documents"))
+            }
+            None if !options.migrations.is_empty() => {
+                Err(syn::Error::new(options.migrations[0].segments[0].ident.span(), "migrations need a version"))
+            }
+            _ => Ok(options),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{DeriveInput, Fields, GenericArgument, PathArguments, Type};
+
+use crate::attrs::{SerdeContainer, SerdeField};
+
+/// `T` of `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::Path(path) => {
+            let segment = path.path.segments.last().filter(|segment| segment.ident == "Option")?;
+            let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return None };
+
+            match arguments.args.first()? {
+                GenericArgument::Type(inner) => Some(inner),
+                _ => None,
+            }
+        }
+        Type::Group(group) => option_inner(&group.elem),
+        _ => None,
+    }
+}
+
+/// `<Name>Builder`, with a setter per field and a `build()` checking required fields and validation rules,
+/// and `Name::builder()`.
+pub fn expand(input: &DeriveInput, fields: &Fields, container: &SerdeContainer) -> syn::Result<TokenStream2> {
+    let (name, vis) = (&input.ident, &input.vis);
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "builders don't support generic structs"));
+    }
+
+    let builder = format_ident!("{}Builder", name);
+    let doc = format!("Builder of [`{}`], from `{}::builder()`.", name, name);
+
+    let mut slots = Vec::new();
+    let mut setters = Vec::new();
+    let mut values = Vec::new();
+    let mut checks = Vec::new();
+    let mut required = Vec::new();
+
+    for field in fields {
+        let Some(ident) = &field.ident else {
+            return Err(syn::Error::new_spanned(field, "builders only support named fields"));
+        };
+        let serde = SerdeField::parse(&field.attrs, ident, |name| match container.rename_all {
+            Some(rule) => rule.field(name),
+            None => name.to_string(),
+        })?;
+
+        if serde.skip || serde.read_only {
+            values.push(quote!(#ident: ::std::default::Default::default()));
+            continue;
+        }
+
+        // Option fields are set to their value, and stay `None` otherwise
+        let (ty, json_name) = (&field.ty, &serde.json_name);
+        let setter = option_inner(ty).unwrap_or(ty);
+        let doc = format!("Sets `{}`.", ident);
+
+        slots.push(quote!(#ident: Option<#setter>));
+        setters.push(quote! {
+            #[doc = #doc]
+            pub fn #ident(mut self, value: impl ::std::convert::Into<#setter>) -> Self {
+                self.#ident = Some(::std::convert::Into::into(value));
+                self
+            }
+        });
+
+        values.push(match (option_inner(ty), serde.default_fn, serde.default) {
+            (Some(_), _, _) => quote!(#ident: self.#ident),
+            (None, Some(function), _) => quote!(#ident: self.#ident.unwrap_or_else(#function)),
+            (None, None, true) => quote!(#ident: self.#ident.unwrap_or_default()),
+            (None, None, false) => {
+                checks.push(quote! {
+                    if self.#ident.is_none() {
+                        errors.push(::doctour_ai::schemas::SchemaError::missing(&name, &schema, #json_name));
+                    }
+                });
+                required.push(ident.clone());
+                quote!(#ident)
+            }
+        });
+    }
+
+    let required = (!required.is_empty()).then(|| quote! {
+        let (name, schema) = (<#name as ::doctour_ai::schemas::Schema>::schema_name(), <#name as ::doctour_ai::schemas::JsonSchema>::subschema());
+        let mut errors = Vec::new();
+        #(#checks)*
+
+        let (#(Some(#required),)*) = (#(self.#required,)*) else {
+            return Err(errors);
+        };
+    });
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Default)]
+        #vis struct #builder {
+            #(#slots,)*
+        }
+
+        impl #builder {
+            #(#setters)*
+
+            /// Builds the value, or fails with an error per missing required field, or else per broken validation rule.
+            pub fn build(self) -> Result<#name, Vec<::doctour_ai::schemas::SchemaError>> {
+                #required
+
+                let value = #name { #(#values,)* };
+                ::doctour_ai::schemas::Validate::validate(&value).map_err(|violations| {
+                    let name = <#name as ::doctour_ai::schemas::Schema>::schema_name();
+                    violations.iter().map(|violation| ::doctour_ai::schemas::SchemaError::violation(&name, violation)).collect::<Vec<_>>()
+                })?;
+
+                Ok(value)
+            }
+        }
+
+        impl #name {
+            /// Builder of this type, whose `build()` checks required fields and validation rules.
+            pub fn builder() -> #builder {
+                #builder::default()
+            }
+        }
+    })
+}
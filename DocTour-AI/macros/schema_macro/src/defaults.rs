@@ -0,0 +1,59 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_quote, Expr, ItemStruct};
+
+/// Takes the `#[default_value(...)]` attributes of a struct's fields, and gives each such field a function
+/// returning its default, used by serde for missing values, and implements `Default` with them.
+/// Fields without one default to their type's default.
+pub fn expand(mut item: ItemStruct) -> syn::Result<TokenStream2> {
+    let name = item.ident.clone();
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&item.generics, "defaults doesn't support generic structs"));
+    }
+
+    let mut functions = Vec::new();
+    let mut values = Vec::new();
+
+    for field in &mut item.fields {
+        let Some(ident) = field.ident.clone() else {
+            return Err(syn::Error::new_spanned(field, "defaults only supports named fields"));
+        };
+
+        let mut default = None;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("default_value")) {
+            default = Some(attr.parse_args::<Expr>()?);
+        }
+        field.attrs.retain(|attr| !attr.path().is_ident("default_value"));
+
+        match default {
+            Some(default) => {
+                let (function, ty) = (format_ident!("__default_{}", ident), &field.ty);
+                let path = format!("{}::{}", name, function);
+
+                field.attrs.push(parse_quote!(#[serde(default = #path)]));
+                functions.push(quote! {
+                    #[doc(hidden)]
+                    pub fn #function() -> #ty {
+                        #default
+                    }
+                });
+                values.push(quote!(#ident: Self::#function()));
+            }
+            None => values.push(quote!(#ident: ::std::default::Default::default())),
+        }
+    }
+
+    Ok(quote! {
+        #item
+
+        impl #name {
+            #(#functions)*
+        }
+
+        impl ::std::default::Default for #name {
+            fn default() -> Self {
+                Self { #(#values),* }
+            }
+        }
+    })
+}
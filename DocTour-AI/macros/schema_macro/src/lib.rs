@@ -2,34 +2,460 @@ extern crate proc_macro;
 extern crate quote;
 extern crate syn;
 
+mod attrs;
+mod builder;
+mod defaults;
+mod patch;
+mod rules;
+
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Data, DataEnum, DeriveInput, Field, Fields, Generics, Ident, ItemStruct, Type};
+
+use attrs::{RenameRule, SchemaOptions, SerdeContainer, SerdeField, Tagging};
+use rules::Rule;
+
+/// Whether `ty` is an `Option`, which serde accepts missing.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.path.segments.last().is_some_and(|segment| segment.ident == "Option"),
+        Type::Group(group) => is_option(&group.elem), // Types passed through `schema!` come wrapped
+        _ => false,
+    }
+}
+
+/// Code for a set of named fields, of a struct or of an enum variant.
+struct NamedFields {
+    /// Expression building the JSON Schema of the object holding the fields.
+    schema: TokenStream2,
+    /// Statements validating the fields found under the JSON pointer `path`.
+    validations: TokenStream2,
+    /// Fields that are (de)serialized.
+    idents: Vec<Ident>,
+}
+
+/// `access` is an expression borrowing a field, from its name.
+fn named_fields<'a>(fields: impl IntoIterator<Item = &'a Field>, rename_all: Option<RenameRule>,
+                    access: impl Fn(&Ident) -> TokenStream2) -> syn::Result<NamedFields> {
+    let mut properties = Vec::new();
+    let mut required = Vec::new();
+    let mut validations = Vec::new();
+    let mut idents = Vec::new();
+
+    for field in fields {
+        let ident = field.ident.as_ref().expect("Named fields have a name");
+        let serde = SerdeField::parse(&field.attrs, ident, |name| match rename_all {
+            Some(rule) => rule.field(name),
+            None => name.to_string(),
+        })?;
+        let rules = Rule::parse_all(&field.attrs)?;
+        if serde.skip {
+            continue;
+        }
+
+        let (json_name, ty, field) = (&serde.json_name, &field.ty, access(ident));
+        // Still serialized, so in the schema, but neither required nor validated, as it is never read
+        if serde.read_only {
+            properties.push(quote! {{
+                let mut schema = <#ty as ::doctour_ai::schemas::JsonSchema>::subschema();
+                if let Some(object) = schema.as_object_mut() {
+                    object.insert(String::from("readOnly"), ::serde_json::Value::Bool(true));
+                }
+                properties.insert(String::from(#json_name), schema);
+            }});
+            continue;
+        }
+
+        let checks = rules.iter().map(|rule| rule.check(&quote!(value), &quote!(&path)));
+        let checks = if is_option(ty) {
+            quote!(if let Some(value) = #field { #(#checks)* })
+        } else {
+            quote!(let value = #field; #(#checks)*)
+        };
+        validations.push(quote! {{
+            let path = ::doctour_ai::schemas::validation::pointer(path, #json_name);
+            #checks
+            (&::doctour_ai::schemas::validation::Nested(#field)).validate_nested(&path, violations);
+        }});
+
+        let default = serde.default_fn.map(|function| quote! {
+            if let (Some(object), Ok(value)) = (schema.as_object_mut(), ::serde_json::to_value(#function())) {
+                object.insert(String::from("default"), value);
+            }
+        });
+        let keywords = (!rules.is_empty()).then(|| {
+            let rules = rules.iter().map(Rule::schema);
+            quote!({
+                let schema = &mut schema;
+                #(#rules)*
+            })
+        });
+        properties.push(quote! {{
+            #[allow(unused_mut)]
+            let mut schema = <#ty as ::doctour_ai::schemas::JsonSchema>::subschema();
+            #keywords
+            #default
+            properties.insert(String::from(#json_name), schema);
+        }});
+        if !serde.default && !is_option(ty) {
+            required.push(json_name.clone());
+        }
+        idents.push(ident.clone());
+    }
+
+    let schema = quote! {{
+        let mut properties = ::serde_json::Map::new();
+        #(#properties)*
+
+        ::serde_json::json!({
+            "type": "object",
+            "properties": properties,
+            "required": [#(#required),*],
+        })
+    }};
+
+    Ok(NamedFields { schema, validations: quote!(#(#validations)*), idents })
+}
+
+/// JSON Schema and validation of a struct.
+fn struct_code(name: &Ident, fields: &Fields, container: &SerdeContainer) -> syn::Result<(TokenStream2, TokenStream2)> {
+    let fields = named_fields(fields.iter().filter(|field| field.ident.is_some()), container.rename_all,
+                              |ident| quote!(&self.#ident))?;
+    let (schema, validations) = (fields.schema, fields.validations);
+
+    let strict = container.deny_unknown_fields.then(|| quote! {
+        schema["additionalProperties"] = ::serde_json::Value::Bool(false);
+    });
+    let schema = quote! {{
+        let mut schema = #schema;
+        schema["title"] = ::serde_json::Value::from(stringify!(#name));
+        #strict
+        schema
+    }};
+
+    Ok((schema, validations))
+}
+
+/// JSON Schema and validation of an enum, as a union of its variants.
+fn enum_code(name: &Ident, data: &DataEnum, container: &SerdeContainer) -> syn::Result<(TokenStream2, TokenStream2)> {
+    let mut variants = Vec::new();
+    let mut arms = Vec::new();
+    let mut units = Vec::new();
+
+    for variant in &data.variants {
+        let serde = SerdeField::parse(&variant.attrs, &variant.ident, |name| match container.rename_all {
+            Some(rule) => rule.variant(name),
+            None => name.to_string(),
+        })?;
+        if serde.skip || serde.read_only {
+            continue;
+        }
+
+        let (ident, json_name) = (&variant.ident, &serde.json_name);
 
-#[proc_macro_derive(Schema)]
+        // Where the variant's content is, relative to the enum's value
+        let content_path = match &container.tagging {
+            Tagging::External => quote!(::doctour_ai::schemas::validation::pointer(path, #json_name)),
+            Tagging::Adjacent(_, content) => quote!(::doctour_ai::schemas::validation::pointer(path, #content)),
+            Tagging::Internal(_) | Tagging::Untagged => quote!(path.to_string()),
+        };
+
+        let content = match &variant.fields {
+            Fields::Unit => {
+                units.push(json_name.clone());
+                arms.push(quote!(Self::#ident => {}));
+                None
+            }
+            Fields::Named(fields) => {
+                let fields = named_fields(&fields.named, None, |ident| quote!(#ident))?;
+                let (idents, validations) = (&fields.idents, &fields.validations);
+                arms.push(quote! {
+                    #[allow(unused_variables)]
+                    Self::#ident { #(#idents,)* .. } => {
+                        let content_path = #content_path;
+                        let path = content_path.as_str();
+                        #validations
+                    }
+                });
+                Some(fields.schema)
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                arms.push(quote! {
+                    Self::#ident(value) => {
+                        (&::doctour_ai::schemas::validation::Nested(value)).validate_nested(&#content_path, violations);
+                    }
+                });
+                Some(quote!(<#ty as ::doctour_ai::schemas::JsonSchema>::subschema()))
+            }
+            Fields::Unnamed(fields) => {
+                return Err(syn::Error::new_spanned(fields, "Schema enums only support unit, newtype and struct variants"));
+            }
+        };
+
+        let content = content.map(|content| quote!((#content))); // json! would take a block for an object
+        variants.push(match (&container.tagging, content) {
+            (Tagging::External, None) => quote!(::serde_json::json!({ "const": #json_name })),
+            (Tagging::External, Some(content)) => quote!(::serde_json::json!({
+                "type": "object",
+                "properties": { #json_name: #content },
+                "required": [#json_name],
+                "additionalProperties": false,
+            })),
+            (Tagging::Internal(tag), None) | (Tagging::Adjacent(tag, _), None) => quote!(::serde_json::json!({
+                "type": "object",
+                "properties": { #tag: { "const": #json_name } },
+                "required": [#tag],
+            })),
+            (Tagging::Internal(tag), Some(content)) => quote! {{
+                let mut content = #content;
+                let tagged = content.get("properties").is_some_and(::serde_json::Value::is_object)
+                    && content.get("required").is_some_and(::serde_json::Value::is_array);
+
+                if tagged {
+                    content["properties"][#tag] = ::serde_json::json!({ "const": #json_name });
+                    if let Some(required) = content["required"].as_array_mut() {
+                        required.insert(0, ::serde_json::Value::from(#tag));
+                    }
+                    content
+                } else { // Content that isn't an object schema, e.g. a map
+                    ::serde_json::json!({
+                        "allOf": [content, { "type": "object", "properties": { #tag: { "const": #json_name } }, "required": [#tag] }],
+                    })
+                }
+            }},
+            (Tagging::Adjacent(tag, content_name), Some(content)) => quote!(::serde_json::json!({
+                "type": "object",
+                "properties": { #tag: { "const": #json_name }, #content_name: #content },
+                "required": [#tag, #content_name],
+            })),
+            (Tagging::Untagged, None) => quote!(::serde_json::json!({ "type": "null" })),
+            (Tagging::Untagged, Some(content)) => content,
+        });
+    }
+
+    let schema = if matches!(container.tagging, Tagging::External) && units.len() == variants.len() {
+        quote!(::serde_json::json!({ "title": stringify!(#name), "type": "string", "enum": [#(#units),*] }))
+    } else {
+        quote!(::serde_json::json!({ "title": stringify!(#name), "oneOf": [#((#variants)),*] }))
+    };
+
+    let validations = quote! {
+        #[allow(unreachable_patterns)]
+        match self {
+            #(#arms)*
+            _ => {}
+        }
+    };
+
+    Ok((schema, validations))
+}
+
+/// `generics` with `bound` added to every type parameter.
+fn with_bound(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    let params: Vec<Ident> = generics.type_params().map(|param| param.ident.clone()).collect();
+
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(parse_quote!(#param: #bound));
+    }
+
+    generics
+}
+
+/// Implements `Schema`, `JsonSchema` and `Validate` for a struct with named fields, or an enum.
+///
+/// The JSON Schema of the struct lists its fields under their serde names (`#[serde(rename = "...")]`,
+/// or `#[serde(rename_all = "...")]` on the struct). Every field is required, except `Option`s and fields
+/// with a `#[serde(default)]`. Fields with a `#[serde(skip_deserializing)]` are `readOnly`, and never required.
+///
+/// Fields with a `#[serde(default = "path")]` are documented with their `default` value, and structs with
+/// `#[serde(deny_unknown_fields)]` with `additionalProperties: false`.
+///
+/// The JSON Schema of an enum is a `oneOf` union of its variants, following its serde tagging: external
+/// (the default), internal (`#[serde(tag = "type")]`), adjacent (`#[serde(tag = "type", content = "data")]`)
+/// or `#[serde(untagged)]`, and its variants' renames. Unit, newtype and struct variants are supported.
+///
+/// Fields are validated with the rules of their `#[validate(...)]` attributes: `email`, `url`, `date`,
+/// `range(<range>)`, `len(<range>)` and `each(<rules>)`, which applies rules to every item. Rules of an
+/// `Option` field apply to its value, if any. Fields whose type implements `Validate` are validated too.
+/// The JSON Schema has the rules' keywords: `format`, `minimum`, `maximum` (or `exclusiveMaximum`) and the length
+/// ones, for literal ranges.
+///
+/// Recursive types, e.g. trees with a `Vec<Self>` of children, refer to themselves with a `$ref` to the
+/// `$anchor` of their schema, named after their path.
+///
+/// Generic types are supported, with their lifetimes and where-clauses. Each impl bounds the type parameters
+/// by what it needs: `JsonSchema`, `Validate`, and for `Schema` both plus `Serialize` and `DeserializeOwned`.
+/// Every path is absolute, so the derive works in any module of any crate depending on `doctour_ai`,
+/// `serde` and `serde_json`.
+///
+/// Structs without generics can also get a builder with `#[schema(builder)]`: `Name::builder()` returns a
+/// `<Name>Builder` with a setter per field (taking anything `Into` the field's type, or its `Option`'s),
+/// whose `build()` fails with a `SchemaError` per missing required field, or else per broken validation rule.
+/// Optional fields and fields with a default can be left unset.
+///
+/// `#[schema(version = 3)]` versions the schema: its JSON documents carry the version as `"$v"`, and
+/// `#[schema(migrations(v1_to_v2, v2_to_v3))]` lists the functions upgrading documents of older versions.
+/// Upgraded documents are owned, so versioned types must implement `DeserializeOwned`, borrowing nothing.
+/// `#[schema(version = 3, stamp = false)]` only registers the type under that version, as patches are.
+///
+/// Types without generics are registered in the schema registry, under their name and version, which
+/// requires them to implement `Serialize` and `DeserializeOwned`. They also get a test next to them, run by
+/// `cargo test` in their crate, that round-trips values through JSON and every wire format enabled in `doctour_ai`:
+/// the one of `#[schema(sample = <expr>)]`, and random ones with its `fake` feature. Without either, it's their
+/// default value, and types without `Default` don't compile in tests. Types declared inside a function can't have
+/// tests, and turn it off with `#[schema(round_trip = false)]`.
+#[proc_macro_derive(Schema, attributes(validate, schema))]
 pub fn schema_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     let name = &input.ident; // Struct name
 
+    let code = SerdeContainer::parse(&input.attrs).and_then(|container| {
+        let options = SchemaOptions::parse(&input.attrs)?;
+
+        let code = match &input.data {
+            Data::Struct(data) => (
+                struct_code(name, &data.fields, &container)?,
+                options.builder.then(|| builder::expand(&input, &data.fields, &container)).transpose()?,
+            ),
+            Data::Enum(_) if options.builder => return Err(syn::Error::new(name.span(), "builders only support structs")),
+            Data::Enum(data) => (enum_code(name, data, &container)?, None),
+            Data::Union(_) => return Err(syn::Error::new(name.span(), "Schema can't be derived for unions")),
+        };
+        Ok((code, options))
+    });
+    let (((schema, validations), builder), options) = match code {
+        Ok(code) => code,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl(); // Each impl has its own where-clause
+    let schema_generics = with_bound(&input.generics, quote! {
+        ::doctour_ai::schemas::JsonSchema + ::doctour_ai::schemas::Validate
+            + ::serde::Serialize + ::serde::de::DeserializeOwned
+    });
+    let mut schema_generics = schema_generics;
+    // A version without a stamp only tells the registry apart, like a patch's
+    let stamped = options.version.as_ref().filter(|_| options.stamp);
+    if stamped.is_some() { // Upgraded documents are owned
+        schema_generics.make_where_clause().predicates.push(parse_quote!(#name #ty_generics: ::serde::de::DeserializeOwned));
+    }
+    let schema_where = schema_generics.split_for_impl().2;
+    let json_schema_generics = with_bound(&input.generics, quote!(::doctour_ai::schemas::JsonSchema));
+    let json_schema_where = json_schema_generics.split_for_impl().2;
+    let validate_generics = with_bound(&input.generics, quote!(::doctour_ai::schemas::Validate));
+    let validate_where = validate_generics.split_for_impl().2;
+
+    let version = options.version.as_ref().map(|version| match stamped {
+        Some(_) => {
+            let migrations = &options.migrations;
+            quote! {
+                const VERSION: u32 = #version;
+                const VERSIONED: bool = true;
+                const MIGRATIONS: &'static [::doctour_ai::schemas::Migration] = &[#(#migrations),*];
+
+                fn from_document(document: ::serde_json::Value) -> Result<Self, ::doctour_ai::schemas::SchemaError> {
+                    ::doctour_ai::schemas::migration::from_document(document)
+                }
+            }
+        }
+        None => quote!(const VERSION: u32 = #version;),
+    });
+    // Versioned documents carry their version, which a strict schema must accept too
+    let schema = match stamped {
+        Some(version) => quote! {{
+            let mut schema = #schema;
+            if let Some(properties) = schema.get_mut("properties").and_then(::serde_json::Value::as_object_mut) {
+                properties.insert(String::from("$v"), ::serde_json::json!({ "const": #version }));
+            }
+            schema
+        }},
+        None => schema,
+    };
+    let registration = input.generics.params.is_empty().then(|| quote! {
+        ::doctour_ai::schemas::registry::inventory::submit! {
+            ::doctour_ai::schemas::registry::Entry::new::<#name>(stringify!(#name), module_path!())
+        }
+    });
+
+    // A test function next to the type rather than a module, which would have to reach it through `super`
+    let round_trip = (options.round_trip && input.generics.params.is_empty()).then(|| {
+        let test = format_ident!("__{}_round_trip", name);
+        let sample = options.sample.as_ref().map(|sample| quote!(, #sample));
+        quote!(::doctour_ai::__round_trip_test!(#test, #name #sample);)
+    });
+
     // Generate the implementation
     let expanded = quote! {
-        impl Schema for #name {
-            fn to_json(&self) -> Result<String, JsonError>
-            where
-                Self: Serialize,
-            {
-                serde_json::to_string(self)
+        impl #impl_generics ::doctour_ai::schemas::Schema for #name #ty_generics #schema_where {
+            #version
+        }
+
+        impl #impl_generics ::doctour_ai::schemas::JsonSchema for #name #ty_generics #json_schema_where {
+            fn subschema() -> ::serde_json::Value {
+                ::doctour_ai::schemas::json_schema::recursive(::std::any::type_name::<Self>(), || #schema)
             }
+        }
 
-            fn from_json<'b>(json: &'b str) -> Result<Self, JsonError>
-            where
-                Self: Deserialize<'b>,
-            {
-                serde_json::from_str(json)
+        impl #impl_generics ::doctour_ai::schemas::Validate for #name #ty_generics #validate_where {
+            #[allow(unused_variables)]
+            fn validate_at(&self, path: &str, violations: &mut Vec<::doctour_ai::schemas::Violation>) {
+                #[allow(unused_imports)]
+                use ::doctour_ai::schemas::validation::{NestedValidate, NotValidated};
+
+                #validations
             }
         }
+
+        #builder
+
+        #registration
+
+        #round_trip
     };
 
     TokenStream::from(expanded)
 }
+
+/// Gives the fields of a struct the defaults of their `#[default_value(...)]` attributes: serde fills missing
+/// fields with them, and the struct implements `Default` with them (other fields get their type's default).
+/// `schema!` adds it to every struct, with the values written after `=`.
+///
+/// # Example
+///
+/// ```rust, ignore
+/// #[defaults]
+/// #[derive(serde::Deserialize)]
+/// pub struct Search {
+///     pub query: String,
+///     #[default_value(5)]
+///     pub top_k: u32,
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn defaults(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemStruct);
+
+    match defaults::expand(item) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Adds `<Name>Patch` next to a struct: its fields, with their serde names, all wrapped in an `Option`
+/// that is `None` when the field is missing, and implements `Patchable` for the struct with it.
+/// `Option` fields become `Option<Option<T>>`, so `null` (`Some(None)`) clears them.
+/// `schema!` adds it to every struct.
+#[proc_macro_attribute]
+pub fn patch(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemStruct);
+
+    match patch::expand(item) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
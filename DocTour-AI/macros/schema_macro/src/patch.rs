@@ -0,0 +1,79 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::ItemStruct;
+
+use crate::attrs::SchemaOptions;
+use crate::is_option;
+
+/// Keeps a struct as it is, and adds `<Name>Patch`: the same fields, with the same serde names, all optional
+/// and skipped when `None`. It implements `Patchable` for the struct with it.
+pub fn expand(item: ItemStruct) -> syn::Result<TokenStream2> {
+    let (name, vis) = (&item.ident, &item.vis);
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&item.generics, "patch doesn't support generic structs"));
+    }
+
+    let patch = format_ident!("{}Patch", name);
+    let container: Vec<_> = item.attrs.iter().filter(|attr| attr.path().is_ident("serde")).collect();
+    let options = SchemaOptions::parse(&item.attrs)?;
+    let round_trip = (!options.round_trip).then(|| quote!(#[schema(round_trip = false)]));
+    // Registered under the struct's version, without carrying it: a patch isn't a document
+    let version = options.version.map(|version| quote!(#[schema(version = #version, stamp = false)]));
+    let doc = format!("[`{}`] with every field optional, for `Patchable::apply_patch`.", name);
+
+    let mut fields = Vec::new();
+    let mut updates = Vec::new();
+
+    for field in &item.fields {
+        let Some(ident) = &field.ident else {
+            return Err(syn::Error::new_spanned(field, "patch only supports named fields"));
+        };
+
+        // Only renames: the patch's fields have their own default
+        let renames = field.attrs.iter().filter(|attr| {
+            let mut rename = false;
+            if attr.path().is_ident("serde") {
+                let _ = attr.parse_nested_meta(|meta| {
+                    rename |= meta.path.is_ident("rename");
+                    Ok(())
+                });
+            }
+            rename
+        });
+
+        let (ty, field_vis) = (&field.ty, &field.vis);
+        let nullable = is_option(ty).then(|| quote!(#[serde(deserialize_with = "::doctour_ai::schemas::patch::double_option")]));
+        fields.push(quote! {
+            #(#renames)*
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            #nullable
+            #field_vis #ident: Option<#ty>,
+        });
+        updates.push(quote! {
+            if let Some(value) = patch.#ident {
+                self.#ident = value;
+            }
+        });
+    }
+
+    Ok(quote! {
+        #item
+
+        #[doc = #doc]
+        #[derive(Debug, Clone, Default, PartialEq, ::doctour_ai::schemas::Schema, ::serde::Serialize, ::serde::Deserialize)]
+        #(#container)*
+        #round_trip
+        #version
+        #vis struct #patch {
+            #(#fields)*
+        }
+
+        impl ::doctour_ai::schemas::Patchable for #name {
+            type Patch = #patch;
+
+            fn apply_patch(&mut self, patch: #patch) {
+                #(#updates)*
+            }
+        }
+    })
+}
@@ -0,0 +1,149 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parenthesized, Attribute, Expr, ExprRange, Ident, RangeLimits, Token};
+
+/// A validation rule of `#[validate(...)]`, written after `where` in `schema!`.
+pub enum Rule {
+    Email,
+    Url,
+    Date,
+    Range(Expr),
+    Len(Expr),
+    Each(Vec<Rule>),
+}
+
+impl Rule {
+    /// All the rules of a field, from its `#[validate(...)]` attributes.
+    pub fn parse_all(attrs: &[Attribute]) -> syn::Result<Vec<Rule>> {
+        let mut rules = Vec::new();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("validate")) {
+            rules.extend(attr.parse_args_with(Punctuated::<Rule, Token![,]>::parse_terminated)?);
+        }
+
+        Ok(rules)
+    }
+
+    /// Code pushing a violation when `value` (a reference) at the JSON pointer `path` breaks this rule.
+    pub fn check(&self, value: &TokenStream2, path: &TokenStream2) -> TokenStream2 {
+        let violation = quote!(::doctour_ai::schemas::Violation::new);
+
+        match self {
+            Rule::Email | Rule::Url | Rule::Date => {
+                let (name, message) = match self {
+                    Rule::Email => ("email", "must be an email address"),
+                    Rule::Url => ("url", "must be an http(s) URL"),
+                    _ => ("date", "must be a date as YYYY-MM-DD"),
+                };
+                let rule = Ident::new(name, Span::call_site());
+
+                quote! {
+                    if !::doctour_ai::schemas::validation::rules::#rule(::std::convert::AsRef::<str>::as_ref(#value)) {
+                        violations.push(#violation(#path, #name, #message));
+                    }
+                }
+            }
+            Rule::Range(range) => {
+                let message = format!("must be in {}", range.to_token_stream().to_string().replace(' ', ""));
+
+                quote! {
+                    if !(#range).contains(#value) {
+                        violations.push(#violation(#path, "range", #message));
+                    }
+                }
+            }
+            Rule::Len(range) => {
+                let message = format!("length must be in {}", range.to_token_stream().to_string().replace(' ', ""));
+
+                quote! {
+                    if !(#range).contains(&::doctour_ai::schemas::validation::Length::length(#value)) {
+                        violations.push(#violation(#path, "len", #message));
+                    }
+                }
+            }
+            Rule::Each(rules) => {
+                let checks = rules.iter().map(|rule| rule.check(&quote!(item), &quote!(&path)));
+
+                quote! {
+                    for (index, item) in #value.iter().enumerate() {
+                        let path = ::doctour_ai::schemas::validation::pointer(#path, index);
+                        #(#checks)*
+                    }
+                }
+            }
+        }
+    }
+
+    /// Statements describing this rule in `schema`, the `&mut Value` of the field's JSON Schema, with the
+    /// keywords `format`, `minimum`, `maximum` and the length ones.
+    pub fn schema(&self) -> TokenStream2 {
+        match self {
+            Rule::Email => quote!(schema["format"] = ::serde_json::Value::from("email");),
+            Rule::Url => quote!(schema["format"] = ::serde_json::Value::from("uri");),
+            Rule::Date => quote!(schema["format"] = ::serde_json::Value::from("date");),
+            Rule::Range(range) => bounds(range, quote!("minimum"), quote!("maximum"), Some(quote!("exclusiveMaximum"))),
+            Rule::Len(range) => {
+                let bounds = bounds(range, quote!(keywords.0), quote!(keywords.1), None);
+
+                quote! {
+                    let keywords = ::doctour_ai::schemas::validation::length_keywords(schema);
+                    #bounds
+                }
+            }
+            Rule::Each(rules) => {
+                let rules = rules.iter().map(Rule::schema);
+
+                quote! {
+                    if let Some(schema) = schema.get_mut("items") {
+                        #(#rules)*
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Statements setting the `minimum` and `maximum` keywords of `range` in `schema`, or `exclusive` for its
+/// exclusive end (its end minus one without it). Ranges which aren't literals, e.g. constants, set none.
+fn bounds(range: &Expr, minimum: TokenStream2, maximum: TokenStream2, exclusive: Option<TokenStream2>) -> TokenStream2 {
+    let Expr::Range(ExprRange { start, limits, end, .. }) = range else { return TokenStream2::new() };
+
+    let start = start.as_ref().map(|start| quote!(schema[#minimum] = ::serde_json::json!(#start);));
+    let end = end.as_ref().map(|end| match (limits, exclusive) {
+        (RangeLimits::Closed(_), _) => quote!(schema[#maximum] = ::serde_json::json!(#end);),
+        (RangeLimits::HalfOpen(_), Some(exclusive)) => quote! {
+            if let Some(object) = schema.as_object_mut() {
+                object.remove(#maximum); // The type's
+            }
+            schema[#exclusive] = ::serde_json::json!(#end);
+        },
+        (RangeLimits::HalfOpen(_), None) => quote!(schema[#maximum] = ::serde_json::json!((#end) - 1);),
+    });
+
+    quote!(#start #end)
+}
+
+impl Parse for Rule {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+
+        match name.to_string().as_str() {
+            "email" => Ok(Rule::Email),
+            "url" => Ok(Rule::Url),
+            "date" => Ok(Rule::Date),
+            "range" | "len" | "each" => {
+                let content;
+                parenthesized!(content in input);
+
+                match name.to_string().as_str() {
+                    "range" => Ok(Rule::Range(content.parse()?)),
+                    "len" => Ok(Rule::Len(content.parse()?)),
+                    _ => Ok(Rule::Each(Punctuated::<Rule, Token![,]>::parse_terminated(&content)?.into_iter().collect())),
+                }
+            }
+            _ => Err(syn::Error::new(name.span(), "expected `email`, `url`, `date`, `range`, `len` or `each`")),
+        }
+    }
+}
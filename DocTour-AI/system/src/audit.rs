@@ -0,0 +1,291 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::logger::utc_timestamp;
+use crate::path::{SysPath, Path, join_root};
+
+static AUDIT: OnceLock<Result<Mutex<AuditLog>, BrokenLink>> = OnceLock::new();
+
+/// Hash the first entry of every chain points to.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// What happened: who asked what, which documents were retrieved and which model and prompt answered.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AuditEvent {
+    pub actor: String,
+    pub question: String,
+    pub documents: Vec<String>,
+    pub model: String,
+    pub prompt_version: String,
+}
+
+/// A line of the audit trail. Its `hash` covers the event and the previous entry's hash,
+/// so editing, removing or reordering any line breaks every link after it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub seq: u64,
+    /// RFC 3339, in UTC, whatever the log format: it's hashed, so it must not change meaning.
+    pub timestamp: String,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn digest(seq: u64, timestamp: &str, event: &AuditEvent, prev_hash: &str) -> String {
+        let content = serde_json::to_string(&(seq, timestamp, event, prev_hash))
+            .expect("Audit events are always serializable");
+
+        Sha256::digest(content.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// First link of the chain that does not hold, as reported by [`AuditLog::verify`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    /// 1-based line of the audit file.
+    pub line: usize,
+    pub reason: String,
+}
+
+impl Display for BrokenLink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Broken audit chain at line {}: {}", self.line, self.reason)
+    }
+}
+
+/// Append-only, hash-chained audit trail. It is kept apart from the diagnostic logs,
+/// in `logs/audit.jsonl`, with one JSON [`AuditEntry`] per line.
+pub struct AuditLog {
+    path: SysPath,
+    seq: u64,
+    last_hash: String,
+}
+
+impl AuditLog {
+    /// Audit trail of the project, in `logs/audit.jsonl`, or why its chain can't be continued.
+    pub fn open<'a>() -> Result<&'a Mutex<AuditLog>, &'a BrokenLink> { Self::get() }
+
+    fn get<'a>() -> Result<&'a Mutex<AuditLog>, &'a BrokenLink> { // Will be unlocked for as long as the MutexGuard is in the caller's scope
+        AUDIT.get_or_init(|| AuditLog::at(join_root!("logs", "audit.jsonl")).map(Mutex::new))
+            .as_ref()
+    }
+
+    /// Audit trail stored at `path`. New entries continue the chain already in the file, if any.
+    /// Fails when the file can't be read, or its last entry can't be or was modified, rather than starting
+    /// another chain or extending a broken one.
+    pub fn at(path: SysPath) -> Result<AuditLog, BrokenLink> {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(BrokenLink { line: 0, reason: format!("Could not read {:?}: {}", path, error) }),
+        };
+
+        let last = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).last();
+        match last {
+            Some((index, line)) => {
+                let entry: AuditEntry = serde_json::from_str(line)
+                    .map_err(|error| BrokenLink { line: index + 1, reason: format!("Unreadable last entry ({})", error) })?;
+                if entry.hash != AuditEntry::digest(entry.seq, &entry.timestamp, &entry.event, &entry.prev_hash) {
+                    return Err(BrokenLink { line: index + 1, reason: format!("Last entry #{} was modified", entry.seq) });
+                }
+
+                Ok(AuditLog { path, seq: entry.seq + 1, last_hash: entry.hash })
+            }
+            None => Ok(AuditLog { path, seq: 0, last_hash: String::from(GENESIS) }),
+        }
+    }
+
+    pub fn path(&self) -> &SysPath {
+        &self.path
+    }
+
+    /// Chains `event` to the previous entry and appends it to the file.
+    pub fn record(&mut self, event: AuditEvent) -> io::Result<AuditEntry> {
+        let timestamp = utc_timestamp();
+        let hash = AuditEntry::digest(self.seq, &timestamp, &event, &self.last_hash);
+        let entry = AuditEntry { seq: self.seq, timestamp, event, prev_hash: self.last_hash.clone(), hash };
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+
+        let line = format!("{}\n", serde_json::to_string(&entry)?);
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+
+        self.seq += 1;
+        self.last_hash = entry.hash.clone();
+
+        Ok(entry)
+    }
+
+    /// Walks the chain from the first entry and reports the first link that does not hold.
+    /// Returns how many entries were verified otherwise.
+    pub fn verify(&self) -> Result<usize, BrokenLink> {
+        Self::verify_file(&self.path)
+    }
+
+    pub fn verify_file(path: &SysPath) -> Result<usize, BrokenLink> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0), // Nothing audited yet
+            Err(error) => return Err(BrokenLink { line: 0, reason: format!("Could not read {:?}: {}", path, error) }),
+        };
+
+        let mut prev_hash = String::from(GENESIS);
+        let mut verified: usize = 0;
+
+        for (index, line) in content.lines().enumerate() {
+            let broken = |reason: String| BrokenLink { line: index + 1, reason };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry: AuditEntry = serde_json::from_str(line)
+                .map_err(|error| broken(format!("Unreadable entry ({})", error)))?;
+
+            if entry.seq != verified as u64 {
+                return Err(broken(format!("Expected entry #{}, found #{}", verified, entry.seq)));
+            }
+
+            if entry.prev_hash != prev_hash {
+                return Err(broken(format!("Entry #{} does not point to the previous entry", entry.seq)));
+            }
+
+            let hash = AuditEntry::digest(entry.seq, &entry.timestamp, &entry.event, &entry.prev_hash);
+            if entry.hash != hash {
+                return Err(broken(format!("Entry #{} was modified", entry.seq)));
+            }
+
+            prev_hash = entry.hash;
+            verified += 1;
+        }
+
+        Ok(verified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(question: &str) -> AuditEvent {
+        AuditEvent {
+            actor: String::from("tester"),
+            question: String::from(question),
+            documents: vec![String::from("doc-1"), String::from("doc-2")],
+            model: String::from("llama2"),
+            prompt_version: String::from("v1"),
+        }
+    }
+
+    /// Empty audit trail in the temporary directory, apart from the project's `logs/`.
+    fn fresh(file_name: &str) -> AuditLog {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), file_name));
+        let _ = fs::remove_file(&path);
+
+        AuditLog::at(path).unwrap()
+    }
+
+    #[test]
+    fn test_chain_is_verified() {
+        let mut audit = fresh("test_audit_chain.jsonl");
+        audit.record(event("First question")).unwrap();
+        audit.record(event("Second question")).unwrap();
+
+        let mut reopened = AuditLog::at(audit.path().clone()).unwrap();
+        let entry = reopened.record(event("Third question")).unwrap();
+
+        assert_eq!(entry.seq, 2);
+        assert_eq!(audit.verify(), Ok(3));
+
+        fs::remove_file(audit.path()).unwrap();
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let mut audit = fresh("test_audit_tampering.jsonl");
+        for question in ["First question", "Second question", "Third question"] {
+            audit.record(event(question)).unwrap();
+        }
+
+        let content = fs::read_to_string(audit.path()).unwrap();
+        fs::write(audit.path(), content.replacen("Second question", "Another question", 1)).unwrap();
+
+        let broken = audit.verify().unwrap_err();
+        assert_eq!(broken.line, 2);
+
+        fs::remove_file(audit.path()).unwrap();
+    }
+
+    #[test]
+    fn test_removed_entry_is_detected() {
+        let mut audit = fresh("test_audit_removal.jsonl");
+        for question in ["First question", "Second question", "Third question"] {
+            audit.record(event(question)).unwrap();
+        }
+
+        let content = fs::read_to_string(audit.path()).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        fs::write(audit.path(), format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+
+        let broken = audit.verify().unwrap_err();
+        assert_eq!(broken.line, 2);
+
+        fs::remove_file(audit.path()).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_last_entry_is_an_error() {
+        let mut audit = fresh("test_audit_unreadable.jsonl");
+        audit.record(event("First question")).unwrap();
+
+        let content = fs::read_to_string(audit.path()).unwrap();
+        fs::write(audit.path(), format!("{}{{\"seq\": 1, trunc\n", content)).unwrap();
+
+        let broken = AuditLog::at(audit.path().clone()).err().unwrap();
+        assert_eq!(broken.line, 2);
+        assert!(broken.reason.starts_with("Unreadable last entry"));
+
+        fs::remove_file(audit.path()).unwrap();
+    }
+
+    #[test]
+    fn test_modified_last_entry_is_an_error() {
+        let mut audit = fresh("test_audit_modified.jsonl");
+        audit.record(event("First question")).unwrap();
+        audit.record(event("Second question")).unwrap();
+
+        let content = fs::read_to_string(audit.path()).unwrap();
+        fs::write(audit.path(), content.replacen("Second question", "Another question", 1)).unwrap();
+
+        let broken = AuditLog::at(audit.path().clone()).err().unwrap();
+        assert_eq!(broken.line, 2);
+        assert_eq!(broken.reason, "Last entry #1 was modified");
+
+        fs::remove_file(audit.path()).unwrap();
+    }
+
+    #[test]
+    fn test_timestamp_is_utc() {
+        let mut audit = fresh("test_audit_timestamp.jsonl");
+        let entry = audit.record(event("First question")).unwrap();
+
+        assert!(chrono::DateTime::parse_from_rfc3339(&entry.timestamp).is_ok());
+        assert!(entry.timestamp.ends_with('Z'));
+
+        fs::remove_file(audit.path()).unwrap();
+    }
+}
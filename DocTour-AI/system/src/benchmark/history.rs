@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::process::Command;
+use std::sync::{OnceLock, PoisonError};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::Configs;
+use crate::logger::utc_timestamp;
+use crate::path::{SysPath, Path, join_root};
+use super::Outcome;
+
+/// A benchmark run, as stored in the history. Runs are compared by `name` on the same `machine`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Run {
+    pub name: String,
+    pub commit: String,
+    pub machine: String,
+    /// RFC 3339, in UTC.
+    pub timestamp: String,
+    /// Duration of a single run, or the median of a statistical one.
+    pub nanos: u64,
+    pub iters: usize,
+    pub status: String,
+}
+
+impl Run {
+    pub fn new(name: &str, nanos: u128, iters: usize, status: &str) -> Run {
+        Run {
+            name: name.to_string(),
+            commit: commit().to_string(),
+            machine: machine().to_string(),
+            timestamp: utc_timestamp(),
+            nanos: u64::try_from(nanos).unwrap_or(u64::MAX),
+            iters,
+            status: status.to_string(),
+        }
+    }
+}
+
+/// Commit the code was built from: `GIT_COMMIT` if set (e.g. on CI), otherwise asked to git.
+pub fn commit() -> &'static str {
+    static COMMIT: OnceLock<String> = OnceLock::new();
+
+    COMMIT.get_or_init(|| {
+        std::env::var("GIT_COMMIT").ok()
+            .or_else(|| Command::new("git")
+                .args(["rev-parse", "--short", "HEAD"])
+                .current_dir(Path::join_root(vec![]))
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string()))
+            .unwrap_or_else(|| String::from("unknown"))
+    })
+}
+
+/// Short fingerprint of the machine: host name, OS, architecture and number of CPUs.
+/// Timings are only comparable on the same machine.
+pub fn machine() -> &'static str {
+    static MACHINE: OnceLock<String> = OnceLock::new();
+
+    MACHINE.get_or_init(|| {
+        let host = fs::read_to_string("/etc/hostname").ok()
+            .or_else(|| std::env::var("HOSTNAME").ok())
+            .or_else(|| std::env::var("COMPUTERNAME").ok())
+            .unwrap_or_default();
+        let cpus = std::thread::available_parallelism().map(usize::from).unwrap_or(1);
+        let description = format!("{}|{}|{}|{}", host.trim(), std::env::consts::OS, std::env::consts::ARCH, cpus);
+
+        Sha256::digest(description.as_bytes())[..6]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    })
+}
+
+/// Whether runs should be appended to the history, as set in the configs.json file.
+pub fn enabled() -> bool {
+    let config = Configs::open().lock().unwrap_or_else(PoisonError::into_inner);
+    config.benchmark().history
+}
+
+/// Percentage above which a slower run is a regression, as set in the configs.json file.
+pub fn threshold() -> f64 {
+    let config = Configs::open().lock().unwrap_or_else(PoisonError::into_inner);
+    config.benchmark().threshold
+}
+
+/// JSON-lines file of benchmark runs.
+pub struct History {
+    path: SysPath,
+}
+
+impl History {
+    /// Every run recorded so far, in `benchmarks/history.jsonl`.
+    pub fn open() -> History {
+        History::at(join_root!("benchmarks", "history.jsonl"))
+    }
+
+    /// Runs the others are compared with, in `benchmarks/baseline.jsonl`.
+    pub fn baseline() -> History {
+        History::at(join_root!("benchmarks", "baseline.jsonl"))
+    }
+
+    pub fn at(path: SysPath) -> History {
+        History { path }
+    }
+
+    pub fn path(&self) -> &SysPath {
+        &self.path
+    }
+
+    pub fn append(&self, run: &Run) -> io::Result<()> {
+        if let Some(folder) = self.path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+
+        let line = format!("{}\n", serde_json::to_string(run)?);
+        file.write_all(line.as_bytes())
+    }
+
+    /// Every run, oldest first. Unreadable lines are skipped.
+    pub fn runs(&self) -> Vec<Run> {
+        fs::read_to_string(&self.path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// Most recent run of each benchmark on `machine`, sorted by name.
+    pub fn latest(&self, machine: &str) -> Vec<Run> {
+        let mut latest: BTreeMap<String, Run> = BTreeMap::new();
+
+        for run in self.runs().into_iter().filter(|run| run.machine == machine) {
+            latest.insert(run.name.clone(), run);
+        }
+
+        latest.into_values().collect()
+    }
+
+    /// Replaces the content of this file with `runs`.
+    pub fn replace(&self, runs: &[Run]) -> io::Result<()> {
+        if let Some(folder) = self.path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+
+        let lines: String = runs.iter()
+            .filter_map(|run| serde_json::to_string(run).ok())
+            .map(|line| format!("{}\n", line))
+            .collect();
+
+        fs::write(&self.path, lines)
+    }
+}
+
+/// How a benchmark's latest run compares with its baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Regressed,
+    Improved,
+    Unchanged,
+    /// No baseline to compare with.
+    New,
+    /// In the baseline, but not run anymore.
+    Removed,
+    /// Ended with an error, so its time isn't comparable.
+    Failed,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Regressed => "REGRESSED",
+            Verdict::Improved => "improved",
+            Verdict::Unchanged => "ok",
+            Verdict::New => "new",
+            Verdict::Removed => "removed",
+            Verdict::Failed => "FAILED",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub name: String,
+    pub baseline: Option<u64>,
+    pub latest: Option<u64>,
+    /// Relative change from the baseline, in percent.
+    pub change: Option<f64>,
+    pub verdict: Verdict,
+}
+
+/// Latest runs compared with the baseline, one row per benchmark, then one per benchmark of the baseline that
+/// wasn't run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub threshold: f64,
+    pub rows: Vec<Row>,
+}
+
+impl Comparison {
+    /// Compares the `latest` runs with the `baseline` ones of the same `machine`.
+    pub fn new(baseline: &[Run], latest: &[Run], machine: &str, threshold: f64) -> Comparison {
+        let baseline: Vec<&Run> = baseline.iter().filter(|base| base.machine == machine).collect();
+        let failed = |run: &Run| run.status != Outcome::Ok.as_str();
+
+        let mut rows: Vec<Row> = latest.iter()
+            .filter(|run| run.machine == machine)
+            .map(|run| {
+                // A failed baseline has nothing to compare with either
+                let base = baseline.iter()
+                    .find(|base| base.name == run.name && !failed(base))
+                    .map(|base| base.nanos);
+
+                let change = base.filter(|&base| base > 0)
+                    .map(|base| (run.nanos as f64 - base as f64) / base as f64 * 100.0);
+
+                let verdict = match change {
+                    _ if failed(run) => Verdict::Failed,
+                    None => Verdict::New,
+                    Some(change) if change > threshold => Verdict::Regressed,
+                    Some(change) if change < -threshold => Verdict::Improved,
+                    Some(_) => Verdict::Unchanged,
+                };
+
+                Row { name: run.name.clone(), baseline: base, latest: Some(run.nanos), change, verdict }
+            })
+            .collect();
+
+        rows.extend(baseline.iter()
+            .filter(|base| !latest.iter().any(|run| run.name == base.name && run.machine == machine))
+            .map(|base| Row { name: base.name.clone(), baseline: Some(base.nanos), latest: None, change: None, verdict: Verdict::Removed }));
+
+        Comparison { threshold, rows }
+    }
+
+    /// Rows failing the comparison: slower than the threshold allows, or failed.
+    pub fn regressions(&self) -> Vec<&Row> {
+        self.rows.iter()
+            .filter(|row| matches!(row.verdict, Verdict::Regressed | Verdict::Failed))
+            .collect()
+    }
+}
+
+/// Nanoseconds in the most readable unit.
+fn human(nanos: u64) -> String {
+    match nanos {
+        0..=999 => format!("{}ns", nanos),
+        1_000..=999_999 => format!("{:.2}µs", nanos as f64 / 1e3),
+        1_000_000..=999_999_999 => format!("{:.2}ms", nanos as f64 / 1e6),
+        _ => format!("{:.2}s", nanos as f64 / 1e9),
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.rows.iter()
+            .map(|row| row.name.chars().count())
+            .chain(["benchmark".len()])
+            .max()
+            .unwrap_or(0);
+
+        writeln!(f, "{:<width$}  {:>12}  {:>12}  {:>9}  status", "benchmark", "baseline", "latest", "change", width = width)?;
+
+        for row in &self.rows {
+            let baseline = row.baseline.map(human).unwrap_or_else(|| String::from("-"));
+            let latest = row.latest.map(human).unwrap_or_else(|| String::from("-"));
+            let change = row.change.map(|change| format!("{:+.1}%", change)).unwrap_or_else(|| String::from("-"));
+
+            writeln!(f, "{:<width$}  {:>12}  {:>12}  {:>9}  {}", row.name, baseline, latest, change,
+                     row.verdict.as_str(), width = width)?;
+        }
+
+        write!(f, "{} regression(s) above {}%", self.regressions().len(), self.threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, nanos: u64) -> Run {
+        Run { nanos, ..Run::new(name, 0, 1, "ok") }
+    }
+
+    #[test]
+    fn test_history() {
+        let history = History::at(std::env::temp_dir().join(format!("test_benchmark_history_{}.jsonl", std::process::id())));
+        let _ = fs::remove_file(history.path());
+
+        history.append(&run("search", 100)).unwrap();
+        history.append(&run("embed", 300)).unwrap();
+        history.append(&run("search", 200)).unwrap();
+        history.append(&Run { machine: String::from("elsewhere"), ..run("search", 1) }).unwrap();
+
+        let latest = history.latest(machine());
+        assert_eq!(latest.iter().map(|run| (run.name.as_str(), run.nanos)).collect::<Vec<_>>(),
+                   vec![("embed", 300), ("search", 200)]);
+
+        fs::remove_file(history.path()).unwrap();
+    }
+
+    #[test]
+    fn test_comparison() {
+        let elsewhere = Run { machine: String::from("elsewhere"), ..run("tokenize", 5) };
+        let baseline = [run("search", 100), run("embed", 1000), run("parse", 50), run("rank", 20), elsewhere];
+        let latest = [run("search", 150), run("embed", 500), run("parse", 52), run("generate", 10)];
+
+        let comparison = Comparison::new(&baseline, &latest, machine(), 10.0);
+        let verdicts: Vec<Verdict> = comparison.rows.iter().map(|row| row.verdict).collect();
+
+        assert_eq!(verdicts, vec![Verdict::Regressed, Verdict::Improved, Verdict::Unchanged, Verdict::New, Verdict::Removed]);
+        assert_eq!(comparison.regressions().len(), 1);
+        assert_eq!(comparison.rows[0].change, Some(50.0));
+        assert_eq!((comparison.rows[4].baseline, comparison.rows[4].latest), (Some(20), None));
+
+        let table = comparison.to_string();
+        assert!(table.contains("REGRESSED"));
+        assert!(table.lines().any(|line| line.starts_with("rank") && line.ends_with("removed")));
+        assert!(table.ends_with("1 regression(s) above 10%"));
+    }
+
+    #[test]
+    fn test_failed_runs() {
+        let failed = |name: &str, nanos: u64| Run { status: String::from("error"), ..run(name, nanos) };
+        let baseline = [run("search", 100), failed("embed", 1000)];
+        let latest = [failed("search", 10), run("embed", 500), failed("generate", 10)];
+
+        let comparison = Comparison::new(&baseline, &latest, machine(), 10.0);
+        let verdicts: Vec<Verdict> = comparison.rows.iter().map(|row| row.verdict).collect();
+
+        assert_eq!(verdicts, vec![Verdict::Failed, Verdict::New, Verdict::Failed]);
+        assert_eq!(comparison.regressions().len(), 2);
+        assert!(comparison.to_string().ends_with("2 regression(s) above 10%"));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let timestamp = run("search", 1).timestamp;
+        assert!(chrono::DateTime::parse_from_rfc3339(&timestamp).is_ok());
+        assert!(timestamp.ends_with('Z'));
+    }
+
+    #[test]
+    fn test_human() {
+        assert_eq!(human(999), "999ns");
+        assert_eq!(human(1_500), "1.50µs");
+        assert_eq!(human(2_000_000), "2.00ms");
+        assert_eq!(human(3_000_000_000), "3.00s");
+    }
+}
@@ -0,0 +1,636 @@
+pub mod history;
+pub mod profiler;
+
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::logger::{Level, Logger};
+use crate::memory::{Tracker, Usage};
+use crate::metrics::{Histogram, Kind, Registry, LATENCY_BUCKETS};
+use history::{History, Run};
+
+/// Times a block of code and reports how long it took through the [`Logger`], as a structured
+/// line with the benchmark's name, duration and result status. Every run time also goes to the
+/// `benchmark_duration_seconds` histogram of the metrics [`Registry`], and, when the history is
+/// turned on, to `benchmarks/history.jsonl` (see [`history`]). While the [`profiler::Profiler`]
+/// runs, the block is also a span of the call tree.
+///
+/// The benchmark is named after the function it is in, e.g. `core::rag::search`, or by `name`, which
+/// blocks of the same function need to be told apart. The name labels the metric, so it must not
+/// change from a run to the next.
+///
+/// By default the block runs once. For fast code, the statistical mode runs it `iters` times,
+/// after `warmup` untimed runs, and reports the distribution in nanoseconds (see [`Stats`]).
+/// In both modes the value of the (last) run is returned. A block evaluating to an `Err` is
+/// reported with the `error` status, run by run. With the `alloc-tracking` feature, the memory
+/// allocated by the block is reported too (see [`Tracker`]).
+///
+/// ```rust, ignore
+/// let tokens = benchmark!(tokenize(text));
+/// let tokens = benchmark!(name = "tokenize"; tokenize(text));
+/// let tokens = benchmark!(name = "tokenize", iters = 1000, warmup = 50; tokenize(text));
+/// ```
+#[macro_export]
+macro_rules! benchmark {
+    (name = $name:expr, iters = $iters:expr, warmup = $warmup:expr; $($token:tt)+) => {
+        {
+            #[allow(unused_imports)]
+            use $crate::benchmark::{AnyOutcome, ResultOutcome};
+
+            let _name: &'static str = $name;
+            let _span = $crate::benchmark::profiler::Span::enter(_name);
+            let (_result, _stats) = $crate::benchmark::measure(_name, $iters, $warmup, || {
+                $($token)+
+            }, |_value| (&$crate::benchmark::Probe(_value)).outcome());
+            drop(_span);
+            $crate::benchmark::report_stats(&_stats);
+
+            _result
+        }
+    };
+    (name = $name:expr, iters = $iters:expr; $($token:tt)+) => {
+        $crate::benchmark!(name = $name, iters = $iters, warmup = 0; $($token)+)
+    };
+    (iters = $iters:expr, warmup = $warmup:expr; $($token:tt)+) => {
+        $crate::benchmark!(name = $crate::function!(), iters = $iters, warmup = $warmup; $($token)+)
+    };
+    (iters = $iters:expr; $($token:tt)+) => {
+        $crate::benchmark!(name = $crate::function!(), iters = $iters, warmup = 0; $($token)+)
+    };
+    (name = $name:expr; $($token:tt)+) => {
+        {
+            #[allow(unused_imports)]
+            use $crate::benchmark::{AnyOutcome, ResultOutcome};
+
+            let _name: &'static str = $name;
+            let _span = $crate::benchmark::profiler::Span::enter(_name);
+            let _tracker = $crate::memory::Tracker::start();
+            let _instant = std::time::Instant::now();
+            let _result = {
+                $($token)+
+            };
+
+            let _elapsed = _instant.elapsed();
+            drop(_span);
+            let _usage = _tracker.finish();
+            $crate::benchmark::report(_name, _elapsed, (&$crate::benchmark::Probe(&_result)).outcome(), _usage);
+
+            _result
+        }
+    };
+    ($($token:tt)+) => {
+        $crate::benchmark!(name = $crate::function!(); $($token)+)
+    };
+}
+
+/// Path of the function it is expanded in, e.g. `core::rag::search`, without the closures it may be in.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! function {
+    () => {
+        {
+            fn _here() {}
+            let mut path: &'static str = ::std::any::type_name_of_val(&_here).trim_end_matches("::_here");
+            while let Some(outer) = path.strip_suffix("::{{closure}}") {
+                path = outer;
+            }
+            path
+        }
+    };
+}
+
+/// Whether a benchmarked run succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    Error,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Error => "error",
+        }
+    }
+}
+
+/// Wraps a benchmarked value so its [`Outcome`] can be told apart by type, without knowing it beforehand:
+/// `(&Probe(&value)).outcome()` picks [`ResultOutcome`] for a `Result` and [`AnyOutcome`] for anything else.
+pub struct Probe<'a, T>(pub &'a T);
+
+pub trait ResultOutcome {
+    fn outcome(&self) -> Outcome;
+}
+
+impl<T, E> ResultOutcome for Probe<'_, Result<T, E>> {
+    fn outcome(&self) -> Outcome {
+        match self.0 {
+            Ok(_) => Outcome::Ok,
+            Err(_) => Outcome::Error,
+        }
+    }
+}
+
+pub trait AnyOutcome {
+    fn outcome(&self) -> Outcome;
+}
+
+impl<T> AnyOutcome for &Probe<'_, T> {
+    fn outcome(&self) -> Outcome {
+        Outcome::Ok
+    }
+}
+
+/// Name of the histogram every benchmarked run time is observed into, labeled by `name` and `status`.
+pub const DURATION_METRIC: &str = "benchmark_duration_seconds";
+
+/// Appends the run built by `run` to the history, when it is turned on in the configs.json file. It is only
+/// built then, as it asks git for the commit and reads the host name.
+fn persist(run: impl FnOnce() -> Run) {
+    if history::enabled() {
+        if let Err(error) = History::open().append(&run()) {
+            crate::log!(Warn, format!("Could not save the benchmark history: {}", error), true);
+        }
+    }
+}
+
+/// Observes every run time, in nanoseconds, in the series of `name` and the run's outcome.
+fn observe(name: &str, runs: impl IntoIterator<Item = (u128, Outcome)>) {
+    let registry = Registry::open();
+    registry.describe(DURATION_METRIC, Kind::Histogram, "Run time of the benchmarked functions and blocks.");
+
+    let mut histograms: Vec<(Outcome, Arc<Histogram>)> = Vec::new();
+    for (nanos, outcome) in runs {
+        let histogram = match histograms.iter().find(|(other, _)| *other == outcome) {
+            Some((_, histogram)) => histogram.clone(),
+            None => {
+                let histogram = registry.histogram(DURATION_METRIC, &[("name", name), ("status", outcome.as_str())], &LATENCY_BUCKETS);
+                histograms.push((outcome, histogram.clone()));
+                histogram
+            }
+        };
+
+        histogram.observe(nanos as f64 / 1e9);
+    }
+}
+
+fn usage_fields(usage: Option<Usage>) -> Vec<(&'static str, String)> {
+    let Some(usage) = usage else { return Vec::new() };
+
+    let mut fields = vec![
+        ("alloc_bytes", usage.bytes.to_string()),
+        ("allocs", usage.allocations.to_string()),
+        ("peak_bytes", usage.peak_bytes.to_string()),
+    ];
+    if let Some(peak_rss) = usage.peak_rss {
+        fields.push(("peak_rss_bytes", peak_rss.to_string()));
+    }
+
+    fields
+}
+
+/// Reports a single run of `name`. Used by the default mode of `benchmark!` and `#[benchmark]`.
+pub fn report(name: &str, elapsed: Duration, outcome: Outcome, usage: Option<Usage>) {
+    observe(name, [(elapsed.as_nanos(), outcome)]);
+    persist(|| Run::new(name, elapsed.as_nanos(), 1, outcome.as_str()));
+
+    let mut fields = vec![
+        ("name", name.to_string()),
+        ("duration_ns", elapsed.as_nanos().to_string()),
+        ("status", outcome.as_str().to_string()),
+    ];
+    fields.extend(usage_fields(usage));
+    Logger::log(Level::Info, "benchmark", format!("{} took {}ms", name, elapsed.as_millis()), &fields, true);
+}
+
+/// Reports the runs of a statistical benchmark, each one with its own outcome. It is an error when any of
+/// them failed.
+pub fn report_stats(stats: &Stats) {
+    observe(&stats.name, stats.samples.iter().copied().zip(stats.outcomes.iter().copied()));
+
+    let errors = stats.outcomes.iter().filter(|outcome| **outcome == Outcome::Error).count();
+    let outcome = if errors > 0 { Outcome::Error } else { Outcome::Ok };
+    persist(|| Run::new(&stats.name, stats.median, stats.iters, outcome.as_str()));
+
+    let mut fields = vec![
+        ("name", stats.name.clone()),
+        ("iters", stats.iters.to_string()),
+        ("warmup", stats.warmup.to_string()),
+        ("min_ns", stats.min.to_string()),
+        ("mean_ns", format!("{:.1}", stats.mean)),
+        ("median_ns", stats.median.to_string()),
+        ("p95_ns", stats.p95.to_string()),
+        ("p99_ns", stats.p99.to_string()),
+        ("stddev_ns", format!("{:.1}", stats.stddev)),
+        ("mild_outliers", stats.outliers.mild.to_string()),
+        ("severe_outliers", stats.outliers.severe.to_string()),
+        ("errors", errors.to_string()),
+        ("status", outcome.as_str().to_string()),
+    ];
+    fields.extend(usage_fields(stats.usage));
+    Logger::log(Level::Info, "benchmark", format!("{} took {:.1}ns on average", stats.name, stats.mean), &fields, true);
+}
+
+/// Outliers among the samples, by Tukey's fences: mild ones fall beyond 1.5 times
+/// the interquartile range from the quartiles, severe ones beyond 3 times.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Outliers {
+    pub mild: usize,
+    pub severe: usize,
+}
+
+/// Distribution of the run times of a benchmark, in nanoseconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub name: String,
+    pub iters: usize,
+    pub warmup: usize,
+    pub min: u128,
+    pub max: u128,
+    pub mean: f64,
+    pub median: u128,
+    pub p95: u128,
+    pub p99: u128,
+    pub stddev: f64,
+    pub outliers: Outliers,
+    /// Every run time, in the order they ran.
+    pub samples: Vec<u128>,
+    /// Outcome of every run, in the same order.
+    pub outcomes: Vec<Outcome>,
+    /// Memory used by each run, on average, when tracked.
+    pub usage: Option<Usage>,
+}
+
+impl Stats {
+    pub fn from_samples<T: ToString>(name: T, warmup: usize, samples: &[u128]) -> Stats {
+        assert!(!samples.is_empty(), "A benchmark needs at least one sample");
+
+        let mut sorted: Vec<u128> = samples.to_vec();
+        sorted.sort_unstable();
+
+        let iters = sorted.len();
+        let mean = sorted.iter().sum::<u128>() as f64 / iters as f64;
+        let variance = sorted.iter()
+            .map(|&sample| (sample as f64 - mean).powi(2))
+            .sum::<f64>() / iters as f64;
+
+        Stats {
+            name: name.to_string(),
+            iters,
+            warmup,
+            min: sorted[0],
+            max: sorted[iters - 1],
+            mean,
+            median: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+            stddev: variance.sqrt(),
+            outliers: outliers(&sorted),
+            samples: samples.to_vec(),
+            outcomes: vec![Outcome::Ok; samples.len()],
+            usage: None,
+        }
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[Benchmark] {} over {} iterations ({} warmup): min={}ns mean={:.1}ns median={}ns p95={}ns p99={}ns stddev={:.1}ns outliers={} mild, {} severe",
+               self.name, self.iters, self.warmup, self.min, self.mean, self.median, self.p95, self.p99,
+               self.stddev, self.outliers.mild, self.outliers.severe)
+    }
+}
+
+/// Nearest-rank percentile of already sorted samples.
+fn percentile(sorted: &[u128], percent: f64) -> u128 {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn outliers(sorted: &[u128]) -> Outliers {
+    let q1 = percentile(sorted, 25.0) as f64;
+    let q3 = percentile(sorted, 75.0) as f64;
+    let iqr = q3 - q1;
+
+    sorted.iter().fold(Outliers::default(), |mut outliers, &sample| {
+        let distance = if (sample as f64) < q1 { q1 - sample as f64 } else { sample as f64 - q3 };
+
+        if distance > 3.0 * iqr { outliers.severe += 1; }
+        else if distance > 1.5 * iqr { outliers.mild += 1; }
+
+        outliers
+    })
+}
+
+/// Runs `f` `warmup` times untimed, then `iters` timed times. Used by the statistical mode of
+/// `benchmark!` and `#[benchmark]`. Returns the value of the last run, and the run times with the `outcome` of each value.
+pub fn measure<T, F, O>(name: &str, iters: usize, warmup: usize, mut f: F, outcome: O) -> (T, Stats)
+    where F: FnMut() -> T, O: Fn(&T) -> Outcome
+{
+    assert!(iters > 0, "A benchmark needs at least one iteration");
+
+    for _ in 0..warmup {
+        std::hint::black_box(f());
+    }
+
+    let mut samples: Vec<u128> = Vec::with_capacity(iters);
+    let mut outcomes: Vec<Outcome> = Vec::with_capacity(iters);
+    let mut result: Option<T> = None;
+    let tracker = Tracker::start();
+
+    for _ in 0..iters {
+        let instant = Instant::now();
+        let value = std::hint::black_box(f());
+        samples.push(instant.elapsed().as_nanos());
+
+        outcomes.push(outcome(&value));
+        result = Some(value);
+    }
+
+    let usage = tracker.finish().map(|usage| usage.per_run(iters as u64));
+    (result.unwrap(), Stats { usage, outcomes, ..Stats::from_samples(name, warmup, &samples) })
+}
+
+/// Same as [`measure`], for async code: each run is timed until the future returned by `f` completes.
+pub async fn measure_async<T, F, Fut, O>(name: &str, iters: usize, warmup: usize, mut f: F, outcome: O) -> (T, Stats)
+    where F: FnMut() -> Fut, Fut: Future<Output = T>, O: Fn(&T) -> Outcome
+{
+    assert!(iters > 0, "A benchmark needs at least one iteration");
+
+    for _ in 0..warmup {
+        std::hint::black_box(f().await);
+    }
+
+    let mut samples: Vec<u128> = Vec::with_capacity(iters);
+    let mut outcomes: Vec<Outcome> = Vec::with_capacity(iters);
+    let mut result: Option<T> = None;
+    let tracker = Tracker::start();
+
+    for _ in 0..iters {
+        let instant = Instant::now();
+        let value = std::hint::black_box(f().await);
+        samples.push(instant.elapsed().as_nanos());
+
+        outcomes.push(outcome(&value));
+        result = Some(value);
+    }
+
+    let usage = tracker.finish().map(|usage| usage.per_run(iters as u64));
+    (result.unwrap(), Stats { usage, outcomes, ..Stats::from_samples(name, warmup, &samples) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use benchmark_macro::benchmark;
+
+    fn stress_test() -> String {
+        for _ in 0..1000000 {
+            let _ = 1 + 1;
+        }
+
+        String::from("I'm stressed!")
+    }
+
+    fn parameterized_stress_test(n: u32) -> String {
+        for _ in 0..n {
+            let _ = 1 + 1;
+        }
+
+        String::from("I'm stressed!")
+    }
+
+    fn multi_parameterized_stress_test(n: u32, m: u32) -> String {
+        for _ in 0..n {
+            for _ in 0..m {
+                let _ = 1 + 1;
+            }
+        }
+
+        String::from("I'm stressed!")
+    }
+
+    #[test]
+    fn test_benchmark() {
+        let result = benchmark! {
+            stress_test()
+        };
+
+        assert_eq!(result, String::from("I'm stressed!"));
+
+        let result = benchmark! {
+            parameterized_stress_test(1000000)
+        };
+
+        assert_eq!(result, String::from("I'm stressed!"));
+
+        let result = benchmark! {
+            multi_parameterized_stress_test(1000, 1000)
+        };
+
+        assert_eq!(result, String::from("I'm stressed!"));
+    }
+
+    #[test]
+    fn test_statistical_benchmark() {
+        let result = benchmark!(iters = 100, warmup = 10; parameterized_stress_test(1000));
+        assert_eq!(result, String::from("I'm stressed!"));
+
+        let result = benchmark!(iters = 10; multi_parameterized_stress_test(10, 10));
+        assert_eq!(result, String::from("I'm stressed!"));
+    }
+
+    #[benchmark(iters = 50, warmup = 5)]
+    fn annotated_stress_test(n: u32, word: String) -> Result<usize, String> {
+        if n == 0 {
+            return Err(word);
+        }
+        Ok((0..n).sum::<u32>() as usize + word.len())
+    }
+
+    #[test]
+    fn test_statistical_benchmark_attribute() {
+        assert_eq!(annotated_stress_test(100, String::from("ok")), Ok(4952));
+        assert!(annotated_stress_test(0, String::from("failed")).is_err());
+
+        let name = concat!(module_path!(), "::annotated_stress_test");
+        assert_eq!(series(name, "ok"), Some(50));
+        assert_eq!(series(name, "error"), Some(50));
+    }
+
+    #[test]
+    fn test_stats() {
+        let samples: Vec<u128> = (1..=100).chain([10_000]).collect();
+        let stats = Stats::from_samples("samples", 0, &samples);
+
+        assert_eq!(stats.iters, 101);
+        assert_eq!(stats.min, 1);
+        assert_eq!(stats.max, 10_000);
+        assert_eq!(stats.median, 51);
+        assert_eq!(stats.p95, 96);
+        assert_eq!(stats.p99, 100);
+        assert_eq!(stats.outliers, Outliers { mild: 0, severe: 1 });
+        assert!((stats.mean - 15050.0 / 101.0).abs() < 1e-9);
+    }
+
+    #[benchmark]
+    fn fallible_stress_test(fail: bool) -> Result<u32, String> {
+        if fail { Err(String::from("I'm too stressed!")) } else { Ok(parameterized_stress_test(10).len() as u32) }
+    }
+
+    fn series(name: &str, status: &str) -> Option<u64> {
+        Registry::open().histograms().into_iter()
+            .find(|(metric, labels, _)| metric == DURATION_METRIC
+                && labels.get("name").map(String::as_str) == Some(name)
+                && labels.get("status").map(String::as_str) == Some(status))
+            .map(|(_, _, snapshot)| snapshot.count)
+    }
+
+    #[test]
+    fn test_benchmark_metrics() {
+        let _ = benchmark!(parameterized_stress_test(7));
+        assert!(series(concat!(module_path!(), "::test_benchmark_metrics"), "ok").unwrap() >= 1);
+
+        let _ = benchmark!(name = "try_from_overflow", iters = 20; u8::try_from(1000u32));
+        assert_eq!(series("try_from_overflow", "error"), Some(20));
+
+        // Every run has its own outcome
+        let mut calls = 0u32;
+        let _ = benchmark!(name = "every_other_run", iters = 10; { calls += 1; u8::try_from(calls * 200) });
+        assert_eq!((series("every_other_run", "ok"), series("every_other_run", "error")), (Some(1), Some(9)));
+
+        assert!(fallible_stress_test(true).is_err());
+        assert!(fallible_stress_test(false).is_ok());
+        let name = concat!(module_path!(), "::fallible_stress_test");
+        assert_eq!(series(name, "error"), Some(1));
+        assert_eq!(series(name, "ok"), Some(1));
+    }
+
+    /// Minimal executor, enough to drive the benchmarked futures to completion.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        use std::pin::pin;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake};
+        use std::thread::{self, Thread};
+
+        struct Unparker(Thread);
+
+        impl Wake for Unparker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Arc::new(Unparker(thread::current())).into();
+        let mut context = Context::from_waker(&waker);
+        let mut future = pin!(future);
+
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    /// Completes on its second poll, like a future waiting on I/O would.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: std::pin::Pin<&mut Self>, context: &mut std::task::Context<'_>) -> std::task::Poll<()> {
+            if self.0 {
+                return std::task::Poll::Ready(());
+            }
+
+            self.0 = true;
+            context.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }
+    }
+
+    #[benchmark]
+    fn early_returning_stress_test(input: &str) -> Result<u32, Box<dyn std::error::Error>> {
+        if input.is_empty() {
+            return Ok(0);
+        }
+
+        let n: u32 = input.parse()?;
+        Ok(n * 2)
+    }
+
+    #[benchmark]
+    async fn async_stress_test(input: &str) -> Result<u32, std::num::ParseIntError> {
+        YieldOnce(false).await;
+        let n: u32 = input.parse()?;
+
+        Ok(n + 1)
+    }
+
+    #[benchmark(iters = 10, warmup = 2)]
+    async fn async_iterated_stress_test(n: u32) -> u32 {
+        YieldOnce(false).await;
+        (0..n).sum()
+    }
+
+    struct Stressed {
+        count: u32,
+    }
+
+    impl Stressed {
+        #[benchmark]
+        fn by_ref(&self) -> u32 {
+            self.count
+        }
+
+        #[benchmark]
+        fn by_mut(&mut self) {
+            self.count += 1;
+        }
+
+        #[benchmark]
+        async fn by_value(self) -> Result<u32, String> {
+            YieldOnce(false).await;
+
+            if self.count > 1 { Ok(self.count) } else { Err(String::from("Not stressed enough")) }
+        }
+    }
+
+    #[test]
+    fn test_early_returns() {
+        assert_eq!(early_returning_stress_test("").unwrap(), 0);
+        assert_eq!(early_returning_stress_test("21").unwrap(), 42);
+        assert!(early_returning_stress_test("NaN").is_err());
+
+        let name = concat!(module_path!(), "::early_returning_stress_test");
+        assert_eq!(series(name, "ok"), Some(2));
+        assert_eq!(series(name, "error"), Some(1));
+    }
+
+    #[test]
+    fn test_async_benchmark() {
+        assert_eq!(block_on(async_stress_test("41")), Ok(42));
+        assert!(block_on(async_stress_test("NaN")).is_err());
+
+        let name = concat!(module_path!(), "::async_stress_test");
+        assert_eq!(series(name, "ok"), Some(1));
+        assert_eq!(series(name, "error"), Some(1));
+
+        assert_eq!(block_on(async_iterated_stress_test(100)), 4950);
+
+        let name = concat!(module_path!(), "::async_iterated_stress_test");
+        assert_eq!(series(name, "ok"), Some(10));
+    }
+
+    #[test]
+    fn test_method_benchmark() {
+        let mut stressed = Stressed { count: 1 };
+        stressed.by_mut();
+        assert_eq!(stressed.by_ref(), 2);
+        assert_eq!(block_on(stressed.by_value()), Ok(2));
+    }
+}
@@ -0,0 +1,382 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use serde_json::json;
+
+use crate::path::SysPath;
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+static SPANS: Mutex<Vec<SpanRecord>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Span the code running on this thread is in: the innermost entered one, or the one of the future being polled.
+    static CURRENT: RefCell<Option<Arc<Node>>> = const { RefCell::new(None) };
+    static THREAD: u64 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+}
+
+/// When the profiler was first started. Every span's start is relative to it.
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// A running span, with a handle on the one it was opened in, so it doesn't depend on the thread it ends on.
+struct Node {
+    name: &'static str,
+    parent: Option<Arc<Node>>,
+    children_ns: AtomicU64,
+}
+
+impl Node {
+    /// Names of the enclosing spans, outermost first, ending with this one.
+    fn stack(&self) -> Vec<String> {
+        let mut names = vec![self.name.to_string()];
+        let mut parent = self.parent.as_deref();
+        while let Some(node) = parent {
+            names.push(node.name.to_string());
+            parent = node.parent.as_deref();
+        }
+        names.reverse();
+
+        names
+    }
+}
+
+/// Makes `node` the current span of this thread, and returns the previous one.
+fn replace_current(node: Option<Arc<Node>>) -> Option<Arc<Node>> {
+    CURRENT.with(|current| current.replace(node))
+}
+
+/// A finished span: one call of a benchmarked function, and where it was called from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanRecord {
+    pub name: String,
+    /// Names of the enclosing spans, outermost first, ending with this one.
+    pub stack: Vec<String>,
+    pub thread: u64,
+    pub start_ns: u64,
+    pub duration_ns: u64,
+    /// Time not spent in nested spans.
+    pub self_ns: u64,
+}
+
+/// Guard timing a call while the [`Profiler`] is running. Opened by `#[benchmark]` and `benchmark!`,
+/// so nested benchmarked calls build a call tree. The span ends when the guard is dropped.
+///
+/// Its parent is the span current when it was opened. Sync code enters it with [`Span::enter`], which makes
+/// it current on the thread until it ends. Futures are wrapped with [`Span::instrument`] instead, so it is
+/// only current while they are polled, whatever the thread, and futures polled in turn don't nest.
+pub struct Span {
+    node: Option<Arc<Node>>,
+    entered: bool,
+    start: Option<Instant>,
+}
+
+impl Span {
+    /// Opens a span, child of the current one, without entering it.
+    pub fn new(name: &'static str) -> Span {
+        if !RUNNING.load(Ordering::Relaxed) {
+            return Span { node: None, entered: false, start: None };
+        }
+
+        let parent = CURRENT.with(|current| current.borrow().clone());
+        let node = Arc::new(Node { name, parent, children_ns: AtomicU64::new(0) });
+
+        Span { node: Some(node), entered: false, start: Some(Instant::now()) }
+    }
+
+    /// Opens a span and makes it the current one of this thread, until it ends.
+    pub fn enter(name: &'static str) -> Span {
+        let mut span = Span::new(name);
+        if span.node.is_some() {
+            replace_current(span.node.clone());
+            span.entered = true;
+        }
+
+        span
+    }
+
+    /// Wraps `future`, so the span is the current one while it is polled.
+    pub fn instrument<F: Future>(&self, future: F) -> Instrumented<F> {
+        Instrumented { node: self.node.clone(), future }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let (Some(node), Some(start)) = (self.node.take(), self.start) else { return };
+        let duration_ns = start.elapsed().as_nanos() as u64;
+
+        // Left alone when the span ends on another thread than the one it was entered on
+        if self.entered && CURRENT.with(|current| current.borrow().as_ref().is_some_and(|current| Arc::ptr_eq(current, &node))) {
+            replace_current(node.parent.clone());
+        }
+        if let Some(parent) = &node.parent {
+            parent.children_ns.fetch_add(duration_ns, Ordering::Relaxed);
+        }
+
+        if RUNNING.load(Ordering::Relaxed) {
+            let record = SpanRecord {
+                name: node.name.to_string(),
+                stack: node.stack(),
+                thread: THREAD.with(|thread| *thread),
+                start_ns: start.saturating_duration_since(epoch()).as_nanos() as u64,
+                duration_ns,
+                self_ns: duration_ns.saturating_sub(node.children_ns.load(Ordering::Relaxed)),
+            };
+            SPANS.lock().unwrap_or_else(PoisonError::into_inner).push(record);
+        }
+    }
+}
+
+/// Future polled with its [`Span`] as the current one, returned by [`Span::instrument`].
+pub struct Instrumented<F> {
+    node: Option<Arc<Node>>,
+    future: F,
+}
+
+/// Restores the previous current span when a poll ends, even by panicking.
+struct Restore(Option<Arc<Node>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        replace_current(self.0.take());
+    }
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `future` is pinned along with `self`, and never moved out of it
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        if this.node.is_none() {
+            return future.poll(context);
+        }
+        let _restore = Restore(replace_current(this.node.clone()));
+
+        future.poll(context)
+    }
+}
+
+/// Collects the spans of benchmarked calls between [`Profiler::start`] and [`Profiler::stop`].
+/// While stopped, spans cost a single atomic load.
+pub struct Profiler;
+
+impl Profiler {
+    pub fn start() {
+        epoch();
+        SPANS.lock().unwrap_or_else(PoisonError::into_inner).clear();
+        RUNNING.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_running() -> bool {
+        RUNNING.load(Ordering::Relaxed)
+    }
+
+    /// Stops collecting and returns every span finished since the start.
+    pub fn stop() -> Profile {
+        RUNNING.store(false, Ordering::Relaxed);
+        let spans = std::mem::take(&mut *SPANS.lock().unwrap_or_else(PoisonError::into_inner));
+
+        Profile { spans }
+    }
+}
+
+/// Spans collected by the [`Profiler`], in the order they finished.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Profile {
+    pub spans: Vec<SpanRecord>,
+}
+
+impl Profile {
+    /// Folded stacks, one `outer;inner;leaf <self time in ns>` line per distinct stack,
+    /// as expected by inferno and flamegraph.pl.
+    pub fn folded(&self) -> String {
+        let mut stacks: BTreeMap<String, u64> = BTreeMap::new();
+
+        for span in &self.spans {
+            let frames: Vec<String> = span.stack.iter().map(|name| name.replace(';', ",")).collect(); // `;` separates frames
+            *stacks.entry(frames.join(";")).or_default() += span.self_ns;
+        }
+
+        stacks.iter()
+            .map(|(stack, nanos)| format!("{} {}\n", stack, nanos))
+            .collect()
+    }
+
+    /// Chrome trace-event JSON, to open in chrome://tracing or Perfetto. Times are in microseconds.
+    pub fn chrome_trace(&self) -> String {
+        let events: Vec<serde_json::Value> = self.spans.iter()
+            .map(|span| json!({
+                "name": span.name,
+                "cat": "benchmark",
+                "ph": "X",
+                "ts": span.start_ns as f64 / 1e3,
+                "dur": span.duration_ns as f64 / 1e3,
+                "pid": std::process::id(),
+                "tid": span.thread,
+            }))
+            .collect();
+
+        json!({ "traceEvents": events }).to_string()
+    }
+
+    pub fn save_folded(&self, path: &SysPath) -> io::Result<()> {
+        fs::write(path, self.folded())
+    }
+
+    pub fn save_chrome_trace(&self, path: &SysPath) -> io::Result<()> {
+        fs::write(path, self.chrome_trace())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use benchmark_macro::benchmark;
+
+    #[benchmark]
+    fn profiled_leaf(n: u64) -> u64 {
+        (0..n).sum()
+    }
+
+    #[benchmark]
+    fn profiled_branch() -> u64 {
+        profiled_leaf(1000) + profiled_leaf(10)
+    }
+
+    #[benchmark]
+    fn profiled_root() -> u64 {
+        profiled_branch() + profiled_leaf(1)
+    }
+
+    /// Tests starting and stopping the profiler run one at a time.
+    static PROFILER: Mutex<()> = Mutex::new(());
+
+    /// Future pending on its first poll, so the task it is in can resume on another thread.
+    struct Yield(bool);
+
+    impl Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            context.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[benchmark]
+    async fn profiled_step(n: u64) -> u64 {
+        Yield(false).await;
+        profiled_leaf(n)
+    }
+
+    #[benchmark]
+    async fn profiled_first() -> u64 {
+        profiled_step(10).await + profiled_step(20).await
+    }
+
+    #[benchmark]
+    async fn profiled_second() -> u64 {
+        profiled_step(30).await
+    }
+
+    fn ours(profile: Profile) -> Profile {
+        let spans = profile.spans.into_iter()
+            .filter(|span| span.stack[0].ends_with("profiled_root"))
+            .collect();
+
+        Profile { spans }
+    }
+
+    #[test]
+    fn test_call_tree() {
+        let _lock = PROFILER.lock().unwrap_or_else(PoisonError::into_inner);
+        Profiler::start();
+        profiled_root();
+        let profile = ours(Profiler::stop());
+
+        assert_eq!(profile.spans.len(), 5);
+
+        let root = profile.spans.last().unwrap();
+        assert_eq!(root.stack.len(), 1);
+        let children: u64 = profile.spans.iter()
+            .filter(|span| span.stack.len() == 2)
+            .map(|span| span.duration_ns)
+            .sum();
+        assert_eq!(root.self_ns, root.duration_ns - children);
+
+        let folded = profile.folded();
+        let stacks: Vec<&str> = folded.lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .map(|stack| stack.rsplit(';').next().unwrap())
+            .collect();
+        assert_eq!(folded.lines().count(), 4);
+        assert!(stacks.iter().all(|leaf| leaf.contains("profiled_")));
+        assert!(folded.contains("profiled_root;system::benchmark::profiler::tests::profiled_branch;"));
+
+        let trace: serde_json::Value = serde_json::from_str(&profile.chrome_trace()).unwrap();
+        assert_eq!(trace["traceEvents"].as_array().unwrap().len(), 5);
+        assert_eq!(trace["traceEvents"][0]["ph"], "X");
+    }
+
+    /// Two tasks polled in turn, each poll on another thread.
+    #[test]
+    fn test_async_call_tree() {
+        let _lock = PROFILER.lock().unwrap_or_else(PoisonError::into_inner);
+        Profiler::start();
+
+        let mut tasks: Vec<Pin<Box<dyn Future<Output = u64> + Send>>> = vec![Box::pin(profiled_first()), Box::pin(profiled_second())];
+        let mut results = Vec::new();
+        while !tasks.is_empty() {
+            let task = tasks.remove(0);
+            let (task, poll) = std::thread::spawn(move || {
+                let mut task = task;
+                let poll = task.as_mut().poll(&mut Context::from_waker(std::task::Waker::noop()));
+                // Nothing stays current on the thread between polls
+                assert!(CURRENT.with(|current| current.borrow().is_none()));
+                (task, poll)
+            }).join().unwrap();
+
+            match poll {
+                Poll::Ready(result) => results.push(result),
+                Poll::Pending => tasks.push(task),
+            }
+        }
+        let profile = Profiler::stop();
+
+        assert_eq!(results.len(), 2);
+        let stacks = |root: &str| -> Vec<Vec<String>> {
+            profile.spans.iter()
+                .filter(|span| span.stack[0].ends_with(root))
+                .map(|span| span.stack.iter().map(|name| name.rsplit("::").next().unwrap().to_string()).collect())
+                .collect()
+        };
+        let (step, leaf) = ("profiled_step", "profiled_leaf");
+        assert_eq!(stacks("profiled_first"), [
+            vec!["profiled_first", step, leaf], vec!["profiled_first", step],
+            vec!["profiled_first", step, leaf], vec!["profiled_first", step],
+            vec!["profiled_first"],
+        ]);
+        assert_eq!(stacks("profiled_second"), [vec!["profiled_second", step, leaf], vec!["profiled_second", step], vec!["profiled_second"]]);
+
+        let first = profile.spans.iter().find(|span| span.stack == [concat!(module_path!(), "::profiled_first")]).unwrap();
+        assert!(first.self_ns <= first.duration_ns);
+    }
+}
@@ -0,0 +1,33 @@
+use std::process::ExitCode;
+
+use system::{AuditLog, BrokenLink, SysPath};
+
+/// Verifies the audit trail: `audit verify [path]`.
+/// Without a path, the project's `logs/audit.jsonl` is checked.
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("verify") => {
+            let result = match args.get(1) {
+                Some(path) => AuditLog::verify_file(&SysPath::from(path)),
+                None => AuditLog::open().map_err(BrokenLink::clone).and_then(|audit| audit.lock().unwrap().verify()),
+            };
+
+            match result {
+                Ok(verified) => {
+                    println!("Audit chain is intact ({} entries)", verified);
+                    ExitCode::SUCCESS
+                }
+                Err(broken) => {
+                    eprintln!("{}", broken);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        _ => {
+            eprintln!("Usage: audit verify [path]");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::process::ExitCode;
+
+use system::benchmark::history::{self, Comparison, History};
+
+/// Compares the benchmark history with its baseline.
+///
+/// * `benchmark compare [--threshold <percent>]` - Prints how the latest run of each benchmark compares
+///   with the baseline, and which ones of the baseline weren't run, and fails when any of them regressed beyond
+///   the threshold or failed.
+/// * `benchmark baseline` - Makes the latest runs the new baseline.
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let latest = History::open().latest(history::machine());
+
+    match args.first().map(String::as_str) {
+        Some("compare") => {
+            let threshold = match args.get(1).map(String::as_str) {
+                Some("--threshold") => match args.get(2).and_then(|threshold| threshold.parse::<f64>().ok()) {
+                    Some(threshold) => threshold,
+                    None => return usage(),
+                },
+                Some(_) => return usage(),
+                None => history::threshold(),
+            };
+
+            let baseline = History::baseline().runs();
+            let comparison = Comparison::new(&baseline, &latest, history::machine(), threshold);
+            println!("{}", comparison);
+
+            if comparison.regressions().is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+        }
+        Some("baseline") => {
+            let baseline = History::baseline();
+            match baseline.replace(&latest) {
+                Ok(()) => {
+                    println!("Saved {} benchmarks as the baseline in {:?}", latest.len(), baseline.path());
+                    ExitCode::SUCCESS
+                }
+                Err(error) => {
+                    eprintln!("Could not save the baseline: {}", error);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        _ => usage(),
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("Usage: benchmark compare [--threshold <percent>] | benchmark baseline");
+    ExitCode::from(2)
+}
@@ -1,8 +1,9 @@
-use std::sync::{Mutex, Once};
+use std::sync::{Mutex, Once, TryLockError};
 use serde::{Deserialize, Serialize};
 
 pub(crate) use crate::env::{config::{Config as Env, Profile}};
 use crate::join_root;
+use crate::logger::{Level, Logger};
 use crate::path::{Path, SysPath};
 
 static SINGLETON: Once = Once::new();
@@ -11,30 +12,105 @@ static mut CONFIGS: Option<Mutex<Configs>> = None;
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Kinds {
     pub trace: bool,
+    #[serde(default = "enabled")]
+    pub debug: bool,
     pub info: bool,
     pub warn: bool,
     pub error: bool,
 }
 
+impl Kinds {
+    pub fn enabled(&self, level: Level) -> bool {
+        match level {
+            Level::Trace => self.trace,
+            Level::Debug => self.debug,
+            Level::Info => self.info,
+            Level::Warn => self.warn,
+            Level::Error => self.error,
+        }
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// How log lines look. Every field is optional in the configs.json file.
+///
+/// `template` placeholders are `{ts}`, `{level}`, `{target}`, `{msg}` and `{fields}`, and each one
+/// accepts a minimum width, e.g. `{level:5}`. `timestamp` is a chrono format string and `timezone`
+/// is either `local`, `utc` or a fixed offset like `-03:00`. Colors are only used on terminals.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Format {
+    pub template: String,
+    pub timestamp: String,
+    pub timezone: String,
+    pub color: bool,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            template: String::from("{ts} {level:5} {target}: {msg} {fields}"),
+            timestamp: String::from("%Y-%m-%d %H:%M:%S"),
+            timezone: String::from("local"),
+            color: true,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Log {
     pub on: bool,
     pub debug: bool,
     pub save: bool,
     pub kinds: Kinds,
+    #[serde(default)]
+    pub format: Format,
+}
+
+/// Benchmark history settings. With `history` on, every benchmark run is appended to
+/// `benchmarks/history.jsonl`, and runs slower than the baseline by more than `threshold`
+/// percent are reported as regressions.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Benchmark {
+    pub history: bool,
+    pub threshold: f64,
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Benchmark {
+            history: false,
+            threshold: 10.0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Configs {
     log: Log,
+    #[serde(default)]
+    benchmark: Benchmark,
     profile: Option<Profile>,
 }
 
 impl Configs {
     pub fn open<'a>() -> &'a Mutex<Configs> { Self::get() }
 
+    /// Whether the configs were already loaded and are not locked at the moment.
+    /// Used by code that must not trigger (or wait on) the configs' initialization, like the panic hook.
+    pub(crate) fn is_available() -> bool {
+        SINGLETON.is_completed() && !matches!(Self::get().try_lock(), Err(TryLockError::WouldBlock))
+    }
+
     fn get<'a>() -> &'a Mutex<Configs> { // Will be unlocked for as long as the MutexGuard is in the caller's scope
         SINGLETON.call_once(|| {
+            // The logger starts with its configs, and so does the logging of panics
+            Logger::install_panic_hook();
+
             unsafe {
                 CONFIGS = Some(Mutex::new(Configs::new()));
             }
@@ -66,6 +142,10 @@ impl Configs {
 
     pub fn debug(&self) -> bool { self.log.debug }
 
+    pub fn benchmark(&self) -> &Benchmark {
+        &self.benchmark
+    }
+
     pub fn profile(&self) -> &Profile {
         self.profile.as_ref().unwrap()
     }
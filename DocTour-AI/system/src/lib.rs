@@ -1,10 +1,20 @@
-mod benchmark;
+extern crate self as system; // So #[benchmark] can be used inside this crate too
+
+pub mod benchmark;
 
 mod path;
 pub use path::{SysPath, Path};
 
 mod logger;
-pub use logger::Logger;
+pub use logger::{Logger, Level};
+
+mod audit;
+pub use audit::{AuditLog, AuditEvent, AuditEntry, BrokenLink};
+
+pub mod metrics;
+pub mod trace;
+
+pub mod memory;
 
 mod config;
 mod env;
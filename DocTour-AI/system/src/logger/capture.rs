@@ -0,0 +1,46 @@
+use std::sync::{Mutex, PoisonError};
+
+use super::format::Record;
+use super::Level;
+
+static LINES: Mutex<Vec<Line>> = Mutex::new(Vec::new());
+
+/// A logged line, kept by the tests whether logging is turned on or not, so they can check what was logged.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Line {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+    /// Name of the thread that logged it, which is the test's name.
+    pub thread: Option<String>,
+}
+
+impl Line {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub(super) fn keep(record: &Record) {
+    let line = Line {
+        level: record.level,
+        target: record.target.to_string(),
+        message: record.message.to_string(),
+        fields: record.fields.iter().map(|(key, value)| (key.to_string(), value.clone())).collect(),
+        thread: std::thread::current().name().map(String::from),
+    };
+
+    LINES.lock().unwrap_or_else(PoisonError::into_inner).push(line);
+}
+
+/// Lines logged so far, by every test, that match `filter`, oldest first.
+pub(crate) fn lines(filter: impl Fn(&Line) -> bool) -> Vec<Line> {
+    LINES.lock().unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter(|line| filter(line))
+        .cloned()
+        .collect()
+}
@@ -41,14 +41,16 @@ impl LoggerEssentials for DebugLogger {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::logger::{Level, Record};
 
     // Make sure log is on and save is true (adjust the system/configs.json file)
     #[test]
     fn test_logger() {
-        DebugLogger::info("Test info message", true);
-        DebugLogger::trace("Test trace message".to_string(), true);
-        DebugLogger::warn(&"Test warning message".to_string(), true);
-        let test: String = String::from("Test error message");
-        DebugLogger::error(test, true);
+        for (level, message) in [(Level::Info, "Test info message"), (Level::Trace, "Test trace message"),
+                                 (Level::Debug, "Test debug message"),
+                                 (Level::Warn, "Test warning message"), (Level::Error, "Test error message")] {
+            let record = Record { level, target: "debug", message, fields: &[] };
+            DebugLogger::log(&record, true);
+        }
     }
 }
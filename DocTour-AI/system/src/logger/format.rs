@@ -0,0 +1,122 @@
+use chrono::{FixedOffset, Local, Utc};
+
+use super::Level;
+use crate::config::Format;
+
+const RESET: &str = "\x1b[0m";
+
+/// A single log line, before it is rendered with the configured [`Format`].
+pub(super) struct Record<'a> {
+    pub level: Level,
+    pub target: &'a str,
+    pub message: &'a str,
+    pub fields: &'a [(&'a str, String)],
+}
+
+impl Format {
+    /// Current time, in the configured format and timezone.
+    /// An unknown timezone falls back to the local one.
+    pub(crate) fn now(&self) -> String {
+        match self.timezone.to_lowercase().as_str() {
+            "local" => Local::now().format(&self.timestamp).to_string(),
+            "utc" => Utc::now().format(&self.timestamp).to_string(),
+            offset => match offset.parse::<FixedOffset>() {
+                Ok(offset) => Utc::now().with_timezone(&offset).format(&self.timestamp).to_string(),
+                Err(_) => Local::now().format(&self.timestamp).to_string(),
+            },
+        }
+    }
+
+    /// Fills the template in with `record`. Only the level is colored, and only when `color` is set.
+    pub(super) fn render(&self, record: &Record, color: bool) -> String {
+        let mut line = String::with_capacity(self.template.len() + record.message.len());
+        let mut rest = self.template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            line.push_str(&rest[..start]);
+
+            let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+                rest = &rest[start..];
+                break;
+            };
+
+            let placeholder = &rest[start + 1..end];
+            let (name, width) = match placeholder.split_once(':') {
+                Some((name, width)) => (name, width.parse::<usize>().unwrap_or(0)),
+                None => (placeholder, 0),
+            };
+
+            let value = match name {
+                "ts" => self.now(),
+                "level" => record.level.as_str().to_string(),
+                "target" => record.target.to_string(),
+                "msg" => record.message.to_string(),
+                "fields" => record.fields.iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                _ => { // Not a placeholder, kept as it is
+                    line.push_str(&rest[start..=end]);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            };
+
+            let value = format!("{:<width$}", value, width = width);
+            if color && name == "level" {
+                line.push_str(&format!("{}{}{}", record.level.color(), value, RESET));
+            } else {
+                line.push_str(&value);
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        line.push_str(rest);
+        line.trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(template: &str) -> Format {
+        Format {
+            template: String::from(template),
+            timestamp: String::from("%Y"),
+            timezone: String::from("utc"),
+            color: true,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let fields = [("took", String::from("3ms")), ("ok", String::from("true"))];
+        let record = Record { level: Level::Info, target: "core", message: "Loaded", fields: &fields };
+
+        let line = format("{level:5}|{target}: {msg} {fields}").render(&record, false);
+        assert_eq!(line, "INFO |core: Loaded took=3ms ok=true");
+
+        let line = format("[{level}] {unknown} {msg}").render(&record, true);
+        assert_eq!(line, format!("[{}INFO{}] {{unknown}} Loaded", Level::Info.color(), RESET));
+    }
+
+    #[test]
+    fn test_render_without_fields_is_trimmed() {
+        let record = Record { level: Level::Warn, target: "", message: "Careful", fields: &[] };
+
+        let line = format("{level} {msg} {fields}").render(&record, false);
+        assert_eq!(line, "WARN Careful");
+    }
+
+    #[test]
+    fn test_timezones() {
+        let year = Utc::now().format("%Y").to_string();
+
+        for timezone in ["utc", "local", "-03:00", "+05:30", "nowhere"] {
+            let format = Format { timezone: String::from(timezone), ..format("") };
+            assert_eq!(format.now().len(), year.len());
+        }
+    }
+}
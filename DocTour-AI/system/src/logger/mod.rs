@@ -1,80 +1,143 @@
 mod debug;
 mod production;
+mod panic;
+mod format;
+#[cfg(test)]
+pub(crate) mod capture;
 
-use super::config::{Configs, Profile};
-use chrono::Local as time;
+use std::io::{IsTerminal, Write};
+use std::sync::PoisonError;
+use super::config::{Configs, Format, Profile};
 
 use debug::DebugLogger;
 use production::ProductionLogger;
+use format::Record;
+
+/// Severity of a log line. Each one can be turned on and off in the configs.json file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
 
-/// Generates the trace, info, warn and error methods that'll be public available
-/// through the Logger interface. Depending on the build's profile, a different Logger
-/// implementation will be called.
-macro_rules! log {
-    ($log_level:ident) => {
-        pub fn $log_level<T: AsRef<str>>(message: T, show: bool) {
-            let profile = {
-                let config = Configs::open().lock().unwrap();
-                config.profile().clone()
-            };
-
-            match profile {
-                Profile::DEBUG => DebugLogger::$log_level(message, show),
-                Profile::PRODUCTION => ProductionLogger::$log_level(message, show)
-            };
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+
+    /// ANSI escape code the level is printed with on terminals.
+    fn color(&self) -> &'static str {
+        match self {
+            Level::Trace => "\x1b[90m",
+            Level::Debug => "\x1b[36m",
+            Level::Info => "\x1b[32m",
+            Level::Warn => "\x1b[33m",
+            Level::Error => "\x1b[1;31m",
         }
+    }
+}
+
+/// Logs `message` at `level` (`Trace`, `Debug`, `Info`, `Warn` or `Error`) through [`Logger::log`], printing
+/// it too when `show` is set. The target of the line is the caller's module.
+///
+/// ```rust, ignore
+/// log!(Warn, format!("Could not save {}", path), true);
+/// ```
+#[macro_export]
+macro_rules! log {
+    ($level:ident, $message:expr, $show:expr) => {
+        $crate::Logger::log($crate::Level::$level, module_path!(), $message, &[], $show)
     };
 }
 
 pub struct Logger;
 
 impl Logger {
-    log!(info);
-    log!(trace);
-    log!(warn);
-    log!(error);
-}
-
-/// Generates the trace, info, warn & error methods for the ILogger trait.
-macro_rules! log_level {
-    ($log_level:ident) => {
-        fn $log_level<T: AsRef<str>>(message: T, show: bool) {
-            let logger: Self = LoggerEssentials::open();
-
-            let (should_log, save, debug): (bool, bool, bool) = {
-                let config = Configs::open().lock().unwrap();
-
-                let should_log = config.log().on && config.log().kinds.$log_level;
-                let save = config.save();
-                let debug = config.debug();
+    /// Logs a structured line: `fields` are rendered as `key=value` pairs after the message.
+    /// Depending on the build's profile, a different Logger implementation will be called.
+    pub fn log<T: AsRef<str>>(level: Level, target: &str, message: T, fields: &[(&str, String)], show: bool) {
+        let profile = {
+            let config = Configs::open().lock().unwrap_or_else(PoisonError::into_inner); // Must keep logging after a panic
+            *config.profile()
+        };
+
+        let record = Record { level, target, message: message.as_ref(), fields };
+        #[cfg(test)]
+        capture::keep(&record);
+
+        match profile {
+            Profile::DEBUG => DebugLogger::log(&record, show),
+            Profile::PRODUCTION => ProductionLogger::log(&record, show)
+        };
+    }
 
-                (should_log, save, debug)
-            };
+    /// Installs a panic hook that records every panic (message, location, thread and backtrace)
+    /// as an error log before the default hook runs. See [`panic::install`].
+    ///
+    /// The logger installs it as it starts, with the configs it reads (see `Configs::open`); this is for
+    /// panics that may happen before anything in `system` ran.
+    pub fn install_panic_hook() {
+        panic::install();
+    }
+}
 
-            if should_log {
-                let timestamp = time::now().format("%Y-%m-%d %H:%M:%S").to_string();
-                let message = format!("[{:?}] {} - {}", stringify!($log_level).to_uppercase(), timestamp, message.as_ref());
-                if save { logger.save(&message); }
+/// Current time as RFC 3339 in UTC, e.g. `2024-05-01T12:00:00.000Z`, for records that outlive the log
+/// format: it sorts and parses the same whatever the configs.json file says.
+pub(crate) fn utc_timestamp() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
 
-                if show {
-                    if debug { dbg!(message); }
-                    else { println!("{}", message); }
+trait ILogger where Self: LoggerEssentials {
+    fn log(record: &Record, show: bool) {
+        let logger: Self = LoggerEssentials::open();
+
+        let (should_log, save, debug, format): (bool, bool, bool, Format) = {
+            let config = Configs::open().lock().unwrap_or_else(PoisonError::into_inner);
+
+            let should_log = config.log().on && config.log().kinds.enabled(record.level);
+            let save = config.save();
+            let debug = config.debug();
+            let format = config.log().format.clone();
+
+            (should_log, save, debug, format)
+        };
+
+        if should_log {
+            if save { logger.save(&format.render(record, false)); }
+
+            if show { // Debug lines go unbuffered to stderr, the others to stdout
+                if debug {
+                    let color = format.color && std::io::stderr().is_terminal();
+                    eprintln!("{}", format.render(record, color));
+                } else {
+                    let color = format.color && std::io::stdout().is_terminal();
+                    println!("{}", format.render(record, color));
                 }
             }
-        }
-    };
-}
 
-trait ILogger where Self: LoggerEssentials {
-    log_level!(info);
-    log_level!(trace);
-    log_level!(warn);
-    log_level!(error);
+            logger.flush();
+        }
+    }
 }
 
 trait LoggerEssentials where Self: Sized {
     fn open() -> Self;
     fn save(&self, message: &String);
+
+    /// Makes sure everything logged so far reached its sink.
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+    }
 }
 
 #[cfg(test)]
@@ -84,10 +147,19 @@ mod tests {
     // Make sure log is on and save is true (adjust the system/configs.json file)
     #[test]
     fn test_logger() {
-        Logger::info("Test info message", true);
-        Logger::trace("Test trace message".to_string(), true);
-        Logger::warn(&"Test warning message".to_string(), true);
+        crate::log!(Info, "Test info message", true);
+        crate::log!(Trace, "Test trace message".to_string(), true);
+        crate::log!(Warn, &"Test warning message".to_string(), true);
         let test: String = String::from("Test error message");
-        Logger::error(test, true);
+        crate::log!(Error, test, true);
+
+        let lines = capture::lines(|line| line.thread.as_deref() == Some("logger::tests::test_logger"));
+        assert_eq!(lines.iter().map(|line| line.level).collect::<Vec<_>>(), vec![Level::Info, Level::Trace, Level::Warn, Level::Error]);
+        assert!(lines.iter().all(|line| line.target == module_path!()));
+    }
+
+    #[test]
+    fn test_structured_logger() {
+        Logger::log(Level::Info, "tests", "Test structured message", &[("answer", 42.to_string())], true);
     }
 }
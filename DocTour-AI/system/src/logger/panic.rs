@@ -0,0 +1,79 @@
+use std::backtrace::Backtrace;
+use std::panic;
+use std::sync::Once;
+use std::thread;
+use std::time::Duration;
+
+use super::{Level, Logger};
+use crate::config::Configs;
+
+static HOOK: Once = Once::new();
+
+/// Chains a hook in front of the current one (usually the default, which prints to stderr).
+/// The panic is logged as an error, with its message, location, thread name and a captured backtrace,
+/// and the sinks are flushed before the previous hook runs and the thread unwinds or the process aborts.
+///
+/// Installing it more than once is a no-op.
+pub(super) fn install() {
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+
+        panic::set_hook(Box::new(move |info| {
+            let payload = info.payload();
+            let message = payload.downcast_ref::<&str>().copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("Box<dyn Any>");
+
+            let location = info.location()
+                .map(|location| format!("{}:{}:{}", location.file(), location.line(), location.column()))
+                .unwrap_or_else(|| String::from("<unknown>"));
+
+            let current = thread::current();
+            let thread = current.name().unwrap_or("<unnamed>");
+
+            let backtrace = Backtrace::force_capture();
+            let report = format!("{}\n{}", message, backtrace);
+            let fields = [("thread", thread.to_string()), ("location", location)];
+
+            // The configs may be the ones panicking (or locked by this very thread), so
+            // logging through them here could deadlock. Another thread only holds them for a moment,
+            // so they're waited on for a while before giving up. The previous hook still reports it.
+            let available = (0..100).any(|_| Configs::is_available() || {
+                thread::sleep(Duration::from_millis(1));
+                false
+            });
+            if available {
+                Logger::log(Level::Error, "panic", report, &fields, false);
+            }
+
+            previous(info);
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logger::capture;
+
+    #[test]
+    fn test_panic_hook() {
+        Configs::open();
+        assert!(HOOK.is_completed()); // Installed with the configs
+        Logger::install_panic_hook();
+
+        let result = thread::Builder::new()
+            .name(String::from("test_panic_hook::panicking"))
+            .spawn(|| panic!("Test panic message"))
+            .unwrap()
+            .join();
+        assert!(result.is_err());
+
+        let lines = capture::lines(|line| line.field("thread") == Some("test_panic_hook::panicking"));
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].level, Level::Error);
+        assert_eq!(lines[0].target, "panic");
+        assert!(lines[0].message.starts_with("Test panic message\n"));
+        assert!(lines[0].field("location").unwrap().contains("panic.rs"));
+    }
+}
@@ -1,15 +1,50 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use chrono::Local as time;
+
 use crate::logger::{ILogger, LoggerEssentials};
+use crate::path::{SysPath, Path, join_root};
 
-pub(super) struct ProductionLogger;
+/// Logger for production builds. This Logger will append the logs to one .txt file per day.
+pub(super) struct ProductionLogger {
+    folder: SysPath,
+    file_name: String,
+}
 
 impl ILogger for ProductionLogger {}
 
 impl LoggerEssentials for ProductionLogger {
     fn open() -> Self {
-        todo!()
+        let date = time::now().format("%Y-%m-%d").to_string();
+        let folder = join_root!("logs");
+
+        ProductionLogger {
+            folder,
+            file_name: format!("production_{}.txt", date),
+        }
+    }
+
+    fn save(&self, message: &String) {
+        let path = self.folder.join(&self.file_name);
+
+        // A failing sink must never take the server down, the message is dropped instead.
+        if let Ok(mut file) = OpenOptions::new().append(true).create(true).open(&path) {
+            let message = format!("{}\n", message);
+            let _ = file.write_all(message.as_bytes());
+        }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logger::{Level, Record};
 
-    fn save(&self, _message: &String) {
-        todo!()
+    #[test]
+    fn test_logger() {
+        for (level, message) in [(Level::Info, "Test info message"), (Level::Error, "Test error message")] {
+            let record = Record { level, target: "production", message, fields: &[] };
+            ProductionLogger::log(&record, true);
+        }
     }
 }
@@ -0,0 +1,236 @@
+#[cfg(feature = "alloc-tracking")]
+use std::alloc::{GlobalAlloc, Layout, System};
+#[cfg(feature = "alloc-tracking")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "alloc-tracking")]
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "alloc-tracking")]
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "alloc-tracking")]
+static LIVE: AtomicU64 = AtomicU64::new(0);
+/// Trackers running at the same time, one bit per slot of [`PEAKS`].
+#[cfg(feature = "alloc-tracking")]
+static ACTIVE: AtomicU64 = AtomicU64::new(0);
+/// Highest live heap size seen by the tracker in each slot, so one tracker starting doesn't reset another's.
+#[cfg(feature = "alloc-tracking")]
+static PEAKS: [AtomicU64; 64] = [const { AtomicU64::new(0) }; 64];
+
+/// Wraps the system allocator, counting every allocation and the live heap size.
+#[cfg(feature = "alloc-tracking")]
+pub struct CountingAllocator;
+
+#[cfg(feature = "alloc-tracking")]
+impl CountingAllocator {
+    fn allocated(size: usize) {
+        let size = size as u64;
+        ALLOCATED.fetch_add(size, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+
+        let live = LIVE.fetch_add(size, Ordering::Relaxed) + size;
+        let mut active = ACTIVE.load(Ordering::Relaxed);
+        while active != 0 {
+            PEAKS[active.trailing_zeros() as usize].fetch_max(live, Ordering::Relaxed);
+            active &= active - 1;
+        }
+    }
+
+    fn freed(size: usize) {
+        LIVE.fetch_sub(size as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "alloc-tracking")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc(layout);
+        if !pointer.is_null() { Self::allocated(layout.size()); }
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout);
+        Self::freed(layout.size());
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc_zeroed(layout);
+        if !pointer.is_null() { Self::allocated(layout.size()); }
+        pointer
+    }
+
+    unsafe fn realloc(&self, pointer: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_pointer = System.realloc(pointer, layout, new_size);
+        if !new_pointer.is_null() { // Counted as a new allocation replacing the old one
+            Self::freed(layout.size());
+            Self::allocated(new_size);
+        }
+        new_pointer
+    }
+}
+
+#[cfg(feature = "alloc-tracking")]
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Memory used by a tracked block. The counters are process-wide, so allocations made by other
+/// threads at the same time are counted too.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Usage {
+    /// Bytes allocated, freed or not.
+    pub bytes: u64,
+    pub allocations: u64,
+    /// Highest live heap size reached, above the one when tracking started.
+    pub peak_bytes: u64,
+    /// Peak resident set size of the process while tracking, where the OS reports it (Linux).
+    pub peak_rss: Option<u64>,
+}
+
+impl Usage {
+    /// Average usage of each of `runs` runs. Peaks are kept as they are.
+    pub fn per_run(&self, runs: u64) -> Usage {
+        let runs = runs.max(1);
+
+        Usage { bytes: self.bytes / runs, allocations: self.allocations / runs, ..*self }
+    }
+}
+
+/// Samples the counters at the start of a block, to report its [`Usage`] at the end.
+///
+/// Trackers can run at the same time, nested or on other threads: each one keeps its own peak, in one of
+/// 64 slots. Past that, a tracker has no slot and reports the growth of the live heap as its peak.
+/// The kernel's peak resident set can only be reset for the whole process, so it's reset by a tracker
+/// starting while no other runs, and the others report it since then. Resetting it is a process-wide side
+/// effect, through `/proc/self/clear_refs` on Linux.
+///
+/// The counting allocator is only installed with the `alloc-tracking` feature, so normal builds keep
+/// the system allocator untouched and [`Tracker::finish`] always returns `None`.
+pub struct Tracker {
+    #[cfg(feature = "alloc-tracking")]
+    start: (u64, u64, u64),
+    #[cfg(feature = "alloc-tracking")]
+    slot: Option<usize>,
+}
+
+impl Tracker {
+    #[cfg(feature = "alloc-tracking")]
+    pub fn start() -> Tracker {
+        let claimed = ACTIVE.fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+            (active != u64::MAX).then(|| active | 1 << active.trailing_ones())
+        });
+        if claimed == Ok(0) {
+            reset_peak_rss();
+        }
+
+        let slot = claimed.ok().map(|active| active.trailing_ones() as usize);
+        let live = LIVE.load(Ordering::Relaxed);
+        if let Some(slot) = slot { // Overwrites what allocations of the slot's last tracker may have raced in
+            PEAKS[slot].store(live, Ordering::Relaxed);
+        }
+
+        Tracker { start: (ALLOCATED.load(Ordering::Relaxed), ALLOCATIONS.load(Ordering::Relaxed), live), slot }
+    }
+
+    #[cfg(not(feature = "alloc-tracking"))]
+    pub fn start() -> Tracker {
+        Tracker {}
+    }
+
+    #[cfg(feature = "alloc-tracking")]
+    pub fn finish(self) -> Option<Usage> {
+        let (allocated, allocations, live) = self.start;
+        let peak = match self.slot {
+            Some(slot) => {
+                // Read before the slot is released, as the next tracker in it resets it
+                let peak = PEAKS[slot].load(Ordering::Relaxed);
+                ACTIVE.fetch_and(!(1 << slot), Ordering::AcqRel);
+                peak
+            }
+            None => LIVE.load(Ordering::Relaxed),
+        };
+
+        Some(Usage {
+            bytes: ALLOCATED.load(Ordering::Relaxed) - allocated,
+            allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+            peak_bytes: peak.saturating_sub(live),
+            peak_rss: peak_rss(),
+        })
+    }
+
+    #[cfg(not(feature = "alloc-tracking"))]
+    pub fn finish(self) -> Option<Usage> {
+        None
+    }
+
+    /// Whether usage is being tracked at all, i.e. the `alloc-tracking` feature is on.
+    pub fn enabled() -> bool {
+        cfg!(feature = "alloc-tracking")
+    }
+}
+
+/// Resets the kernel's peak resident set (VmHWM) to the current one. Best effort.
+///
+/// It writes `/proc/self/clear_refs`, which resets it for the whole process: anything else reading VmHWM,
+/// e.g. a memory report, sees the peak since then instead of since the process started.
+#[cfg(feature = "alloc-tracking")]
+fn reset_peak_rss() {
+    #[cfg(target_os = "linux")]
+    let _ = std::fs::write("/proc/self/clear_refs", "5");
+}
+
+/// Peak resident set since the last reset, in bytes.
+#[cfg(feature = "alloc-tracking")]
+fn peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "alloc-tracking")]
+    #[test]
+    fn test_tracker() {
+        let tracker = Tracker::start();
+        let buffer: Vec<u8> = Vec::with_capacity(1 << 20);
+        std::hint::black_box(&buffer);
+        drop(buffer);
+        let usage = tracker.finish().unwrap();
+
+        assert!(usage.bytes >= 1 << 20);
+        assert!(usage.allocations >= 1);
+        assert!(usage.peak_bytes >= 1 << 20);
+    }
+
+    #[cfg(feature = "alloc-tracking")]
+    #[test]
+    fn test_nested_trackers() {
+        let outer = Tracker::start();
+        let buffer: Vec<u8> = Vec::with_capacity(1 << 20);
+        std::hint::black_box(&buffer);
+        drop(buffer);
+
+        let inner = Tracker::start();
+        std::hint::black_box(vec![0u8; 16]);
+        assert!(inner.finish().unwrap().bytes >= 16);
+
+        assert!(outer.finish().unwrap().peak_bytes >= 1 << 20);
+    }
+
+    #[cfg(not(feature = "alloc-tracking"))]
+    #[test]
+    fn test_tracker() {
+        assert!(!Tracker::enabled());
+        assert_eq!(Tracker::start().finish(), None);
+    }
+
+    #[test]
+    fn test_per_run() {
+        let usage = Usage { bytes: 100, allocations: 10, peak_bytes: 40, peak_rss: None };
+        assert_eq!(usage.per_run(10), Usage { bytes: 10, allocations: 1, peak_bytes: 40, peak_rss: None });
+    }
+}
@@ -0,0 +1,382 @@
+mod prometheus;
+pub use prometheus::CONTENT_TYPE;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+use crate::logger::{Level, Logger};
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// Upper bounds, in seconds, of the default latency buckets. They go from 1µs to 10s.
+pub const LATENCY_BUCKETS: [f64; 15] = [
+    0.000_001, 0.000_01, 0.000_1, 0.000_5, 0.001, 0.002_5, 0.005, 0.01,
+    0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 10.0,
+];
+
+/// Labels of a metric, sorted by name so the same set always identifies the same series.
+pub type Labels = BTreeMap<String, String>;
+
+fn to_labels(labels: &[(&str, &str)]) -> Labels {
+    labels.iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Whether `name` follows the grammar of Prometheus metric names, `[a-zA-Z_:][a-zA-Z0-9_:]*`, or of label names,
+/// the same without colons.
+fn valid_name(name: &str, colons: bool) -> bool {
+    let allowed = |c: char| c == '_' || (colons && c == ':');
+    let mut chars = name.chars();
+
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || allowed(c)) && chars.all(|c| c.is_ascii_alphanumeric() || allowed(c))
+}
+
+/// Why the `name` series with `labels` can't be exported as a `kind`, if it can't.
+fn invalid(name: &str, kind: Kind, labels: &[(&str, &str)]) -> Option<String> {
+    if !valid_name(name, true) {
+        return Some(format!("{:?} is not a valid metric name", name));
+    }
+
+    labels.iter().find_map(|(label, _)| {
+        if !valid_name(label, false) || label.starts_with("__") {
+            Some(format!("{:?} is not a valid label name of {}", label, name))
+        } else if kind == Kind::Histogram && *label == "le" {
+            Some(format!("The le label of {} is reserved to its buckets", name))
+        } else {
+            None
+        }
+    })
+}
+
+/// Value that only goes up, like the number of requests served.
+#[derive(Debug, Default)]
+pub struct Counter {
+    value: AtomicU64,
+}
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, value: u64) {
+        self.value.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// Value that goes up and down, like the number of loaded models.
+#[derive(Debug, Default)]
+pub struct Gauge {
+    bits: AtomicU64, // f64 bits, so it can be updated without a lock
+}
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.bits.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add(&self, value: f64) {
+        let _ = self.bits.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f64::from_bits(bits) + value).to_bits())
+        });
+    }
+
+    pub fn inc(&self) {
+        self.add(1.0);
+    }
+
+    pub fn dec(&self) {
+        self.add(-1.0);
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.bits.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Default)]
+struct HistogramState {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// Distribution of observed values, counted in buckets by their upper bound.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    state: Mutex<HistogramState>,
+}
+
+/// Point-in-time copy of a [`Histogram`]. The bucket counts are cumulative, as in Prometheus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets: Vec<(f64, u64)>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &[f64]) -> Histogram {
+        let state = HistogramState { counts: vec![0; bounds.len()], ..HistogramState::default() };
+
+        Histogram { bounds: bounds.to_vec(), state: Mutex::new(state) }
+    }
+
+    pub fn observe(&self, value: f64) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(bucket) = self.bounds.iter().position(|&bound| value <= bound) {
+            state.counts[bucket] += 1;
+        }
+        state.sum += value;
+        state.count += 1;
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut cumulative: u64 = 0;
+        let buckets = self.bounds.iter()
+            .zip(&state.counts)
+            .map(|(&bound, &count)| {
+                cumulative += count;
+                (bound, cumulative)
+            })
+            .collect();
+
+        HistogramSnapshot { buckets, sum: state.sum, count: state.count }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Histogram => "histogram",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Metric {
+    Counter(Arc<Counter>),
+    Gauge(Arc<Gauge>),
+    Histogram(Arc<Histogram>),
+}
+
+/// Every series sharing a name, which must all be of the same kind.
+#[derive(Debug)]
+struct Family {
+    help: Option<String>,
+    kind: Kind,
+    series: BTreeMap<Labels, Metric>,
+}
+
+/// In-process registry of every metric, for the whole life of the process.
+///
+/// Metric names should follow the Prometheus conventions, e.g. `requests_total` or
+/// `inference_duration_seconds`. Metrics with a name or a label Prometheus doesn't accept, or with a name
+/// already registered as another kind, are left out of it: they still work, but aren't exported, and a
+/// warning tells why, once.
+pub struct Registry {
+    families: Mutex<BTreeMap<String, Family>>,
+    /// Why metrics were left out, each logged once.
+    rejected: Mutex<BTreeSet<String>>,
+}
+
+impl Registry {
+    pub fn open<'a>() -> &'a Registry { Self::get() }
+
+    fn get<'a>() -> &'a Registry {
+        REGISTRY.get_or_init(Registry::new)
+    }
+
+    pub fn new() -> Registry {
+        Registry { families: Mutex::new(BTreeMap::new()), rejected: Mutex::new(BTreeSet::new()) }
+    }
+
+    /// Logs why a metric is left out of the registry, the first time.
+    fn reject(&self, reason: String) {
+        let first = self.rejected.lock().unwrap_or_else(PoisonError::into_inner).insert(reason.clone());
+        if first {
+            Logger::log(Level::Warn, "metrics", reason, &[], true);
+        }
+    }
+
+    /// Why `name` can't be registered as a `kind`, if it can't.
+    fn mismatch(families: &BTreeMap<String, Family>, name: &str, kind: Kind) -> Option<String> {
+        families.get(name)
+            .filter(|family| family.kind != kind)
+            .map(|family| format!("Metric {} is already registered as a {}, not a {}", name, family.kind.as_str(), kind.as_str()))
+    }
+
+    /// Series of the `name` family with `labels`, created with `new` the first time, or an unregistered one.
+    fn metric(&self, name: &str, kind: Kind, labels: &[(&str, &str)], new: impl FnOnce() -> Metric) -> Metric {
+        let mut families = self.families.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(reason) = invalid(name, kind, labels).or_else(|| Self::mismatch(&families, name, kind)) {
+            drop(families);
+            self.reject(reason);
+            return new();
+        }
+
+        families.entry(name.to_string())
+            .or_insert_with(|| Family { help: None, kind, series: BTreeMap::new() })
+            .series.entry(to_labels(labels))
+            .or_insert_with(new)
+            .clone()
+    }
+
+    /// Sets the description shown next to the `name` metric, unless it is registered as another kind.
+    pub fn describe(&self, name: &str, kind: Kind, help: &str) {
+        let mut families = self.families.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(reason) = invalid(name, kind, &[]).or_else(|| Self::mismatch(&families, name, kind)) {
+            drop(families);
+            self.reject(reason);
+            return;
+        }
+
+        let family = families.entry(name.to_string())
+            .or_insert_with(|| Family { help: None, kind, series: BTreeMap::new() });
+        family.help = Some(help.to_string());
+    }
+
+    /// Counter of the `name` series with the given labels, created the first time.
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> Arc<Counter> {
+        match self.metric(name, Kind::Counter, labels, || Metric::Counter(Arc::default())) {
+            Metric::Counter(counter) => counter,
+            _ => unreachable!("Families only hold metrics of their kind"),
+        }
+    }
+
+    /// Gauge of the `name` series with the given labels, created the first time.
+    pub fn gauge(&self, name: &str, labels: &[(&str, &str)]) -> Arc<Gauge> {
+        match self.metric(name, Kind::Gauge, labels, || Metric::Gauge(Arc::default())) {
+            Metric::Gauge(gauge) => gauge,
+            _ => unreachable!("Families only hold metrics of their kind"),
+        }
+    }
+
+    /// Histogram of the `name` series with the given labels, created with `bounds` the first time.
+    pub fn histogram(&self, name: &str, labels: &[(&str, &str)], bounds: &[f64]) -> Arc<Histogram> {
+        match self.metric(name, Kind::Histogram, labels, || Metric::Histogram(Arc::new(Histogram::new(bounds)))) {
+            Metric::Histogram(histogram) => histogram,
+            _ => unreachable!("Families only hold metrics of their kind"),
+        }
+    }
+
+    /// Every histogram series, sorted by name and labels.
+    pub fn histograms(&self) -> Vec<(String, Labels, HistogramSnapshot)> {
+        let families = self.families.lock().unwrap_or_else(PoisonError::into_inner);
+
+        families.iter()
+            .flat_map(|(name, family)| family.series.iter().filter_map(move |(labels, metric)| match metric {
+                Metric::Histogram(histogram) => Some((name.clone(), labels.clone(), histogram.snapshot())),
+                _ => None,
+            }))
+            .collect()
+    }
+
+    /// Every metric, in the Prometheus text exposition format (version 0.0.4).
+    pub fn render(&self) -> String {
+        let families = self.families.lock().unwrap_or_else(PoisonError::into_inner);
+        prometheus::render(&families)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::new(&[1.0, 5.0, 10.0]);
+        for value in [0.5, 1.0, 3.0, 7.0, 20.0] {
+            histogram.observe(value);
+        }
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.buckets, vec![(1.0, 2), (5.0, 3), (10.0, 4)]);
+        assert_eq!(snapshot.count, 5);
+        assert_eq!(snapshot.sum, 31.5);
+    }
+
+    #[test]
+    fn test_registry_series() {
+        let registry = Registry::new();
+        registry.histogram("latency", &[("name", "a")], &LATENCY_BUCKETS).observe(0.1);
+        registry.histogram("latency", &[("name", "a")], &LATENCY_BUCKETS).observe(0.2);
+        registry.histogram("latency", &[("name", "b")], &LATENCY_BUCKETS).observe(0.3);
+
+        let series = registry.histograms();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].1.get("name").map(String::as_str), Some("a"));
+        assert_eq!(series[0].2.count, 2);
+        assert_eq!(series[1].2.count, 1);
+    }
+
+    #[test]
+    fn test_counters_and_gauges() {
+        let registry = Registry::new();
+        registry.counter("requests_total", &[("route", "/chat")]).inc();
+        registry.counter("requests_total", &[("route", "/chat")]).add(2);
+        assert_eq!(registry.counter("requests_total", &[("route", "/chat")]).get(), 3);
+        assert_eq!(registry.counter("requests_total", &[("route", "/search")]).get(), 0);
+
+        let gauge = registry.gauge("loaded_models", &[]);
+        gauge.set(2.0);
+        gauge.inc();
+        gauge.dec();
+        gauge.add(0.5);
+        assert_eq!(registry.gauge("loaded_models", &[]).get(), 2.5);
+    }
+
+    #[test]
+    fn test_kinds_can_not_be_mixed() {
+        let registry = Registry::new();
+        registry.counter("requests_total", &[]).inc();
+        registry.gauge("requests_total", &[]).set(5.0);
+        registry.gauge("requests_total", &[("route", "/chat")]).set(5.0);
+        registry.describe("requests_total", Kind::Gauge, "Requests in flight.");
+
+        assert_eq!(registry.render(), "# TYPE requests_total counter\nrequests_total 1\n");
+        assert_eq!(registry.rejected.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_names_are_left_out() {
+        let registry = Registry::new();
+        registry.counter("requests-total", &[]).inc();
+        registry.counter("requests_total", &[("0route", "/chat")]).inc();
+        registry.counter("requests_total", &[("__name__", "other")]).inc();
+        registry.histogram("latency_seconds", &[("le", "1")], &[1.0]).observe(0.5);
+        registry.describe("latency seconds", Kind::Histogram, "Latency.");
+
+        let counter = registry.counter("rpc:requests_total", &[("route", "/chat")]);
+        counter.inc();
+        assert_eq!(registry.render(), "# TYPE rpc:requests_total counter\nrpc:requests_total{route=\"/chat\"} 1\n");
+        assert_eq!(registry.rejected.lock().unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::{Family, Labels, Metric};
+
+/// Content type of the rendered text, for the `/metrics` response.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn number(value: f64) -> String {
+    match value {
+        value if value.is_nan() => String::from("NaN"),
+        value if value == f64::INFINITY => String::from("+Inf"),
+        value if value == f64::NEG_INFINITY => String::from("-Inf"),
+        value => value.to_string(),
+    }
+}
+
+/// `{name="value",...}`, with `extra` (like a histogram's `le`) last. Empty without labels.
+fn labels(labels: &Labels, extra: Option<(&str, &str)>) -> String {
+    let pairs: Vec<String> = labels.iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .chain(extra)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+
+    if pairs.is_empty() { String::new() } else { format!("{{{}}}", pairs.join(",")) }
+}
+
+pub(super) fn render(families: &BTreeMap<String, Family>) -> String {
+    let mut text = String::new();
+
+    for (name, family) in families.iter().filter(|(_, family)| !family.series.is_empty()) {
+        if let Some(help) = &family.help {
+            let _ = writeln!(text, "# HELP {} {}", name, help.replace('\\', "\\\\").replace('\n', "\\n"));
+        }
+        let _ = writeln!(text, "# TYPE {} {}", name, family.kind.as_str());
+
+        for (series, metric) in &family.series {
+            match metric {
+                Metric::Counter(counter) => {
+                    let _ = writeln!(text, "{}{} {}", name, labels(series, None), counter.get());
+                }
+                Metric::Gauge(gauge) => {
+                    let _ = writeln!(text, "{}{} {}", name, labels(series, None), number(gauge.get()));
+                }
+                Metric::Histogram(histogram) => {
+                    let snapshot = histogram.snapshot();
+
+                    for (bound, count) in &snapshot.buckets {
+                        let _ = writeln!(text, "{}_bucket{} {}", name, labels(series, Some(("le", &number(*bound)))), count);
+                    }
+                    let _ = writeln!(text, "{}_bucket{} {}", name, labels(series, Some(("le", "+Inf"))), snapshot.count);
+                    let _ = writeln!(text, "{}_sum{} {}", name, labels(series, None), number(snapshot.sum));
+                    let _ = writeln!(text, "{}_count{} {}", name, labels(series, None), snapshot.count);
+                }
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::{Kind, Registry};
+
+    #[test]
+    fn test_render() {
+        let registry = Registry::new();
+        registry.describe("requests_total", Kind::Counter, "Requests served.");
+        registry.counter("requests_total", &[("route", "/chat"), ("method", "POST")]).add(3);
+        registry.gauge("queue_depth", &[]).set(1.5);
+        let histogram = registry.histogram("latency_seconds", &[("model", "say \"hi\"")], &[0.1, 1.0]);
+        histogram.observe(0.0625);
+        histogram.observe(0.5);
+        histogram.observe(2.0);
+
+        let expected = concat!(
+            "# TYPE latency_seconds histogram\n",
+            "latency_seconds_bucket{model=\"say \\\"hi\\\"\",le=\"0.1\"} 1\n",
+            "latency_seconds_bucket{model=\"say \\\"hi\\\"\",le=\"1\"} 2\n",
+            "latency_seconds_bucket{model=\"say \\\"hi\\\"\",le=\"+Inf\"} 3\n",
+            "latency_seconds_sum{model=\"say \\\"hi\\\"\"} 2.5625\n",
+            "latency_seconds_count{model=\"say \\\"hi\\\"\"} 3\n",
+            "# TYPE queue_depth gauge\n",
+            "queue_depth 1.5\n",
+            "# HELP requests_total Requests served.\n",
+            "# TYPE requests_total counter\n",
+            "requests_total{method=\"POST\",route=\"/chat\"} 3\n",
+        );
+
+        assert_eq!(registry.render(), expected);
+    }
+}
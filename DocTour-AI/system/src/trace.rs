@@ -0,0 +1,225 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use crate::benchmark::Probe;
+use crate::logger::{Level, Logger};
+
+/// Wraps a traced argument so it can be logged whether it implements `Debug` or not:
+/// `(&Value(&argument)).render()` picks [`DebugValue`] when it does and [`OpaqueValue`] otherwise.
+pub struct Value<'a, T: ?Sized>(pub &'a T);
+
+pub trait DebugValue {
+    fn render(&self) -> String;
+}
+
+impl<T: Debug + ?Sized> DebugValue for Value<'_, T> {
+    fn render(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+pub trait OpaqueValue {
+    fn render(&self) -> String;
+}
+
+impl<T: ?Sized> OpaqueValue for &Value<'_, T> {
+    fn render(&self) -> String {
+        String::from("_")
+    }
+}
+
+/// Error of a traced function's result: `(&&&Probe(&result)).error()` picks [`ResultError`] for a `Result` whose
+/// error implements `Debug`, [`OpaqueError`] for any other `Result`, and [`AnyError`] for a value that isn't one.
+pub trait ResultError {
+    fn error(&self) -> Option<String>;
+}
+
+impl<T, E: Debug> ResultError for &&Probe<'_, Result<T, E>> {
+    fn error(&self) -> Option<String> {
+        self.0.as_ref().err().map(|error| format!("{:?}", error))
+    }
+}
+
+/// An `Err` that can't be printed is still an error, written `_` like the arguments that can't.
+pub trait OpaqueError {
+    fn error(&self) -> Option<String>;
+}
+
+impl<T, E> OpaqueError for &Probe<'_, Result<T, E>> {
+    fn error(&self) -> Option<String> {
+        self.0.as_ref().err().map(|_| String::from("_"))
+    }
+}
+
+pub trait AnyError {
+    fn error(&self) -> Option<String>;
+}
+
+impl<T> AnyError for Probe<'_, T> {
+    fn error(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Logs the call of `name` with its arguments. Used by `#[traced]`.
+pub fn enter(target: &str, name: &str, level: Level, args: &[(&str, String)]) {
+    Logger::log(level, target, format!("enter {}", name), args, true);
+}
+
+/// Logs the end of a call of `name`. A call that ended with an `error` is logged at the error level.
+pub fn exit(target: &str, name: &str, level: Level, elapsed: Duration, error: Option<String>) {
+    let mut fields = vec![("duration_ns", elapsed.as_nanos().to_string())];
+
+    match error {
+        Some(error) => {
+            fields.push(("error", error));
+            Logger::log(Level::Error, target, format!("{} failed after {}ms", name, elapsed.as_millis()), &fields, true);
+        }
+        None => Logger::log(level, target, format!("exit {} after {}ms", name, elapsed.as_millis()), &fields, true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logger::capture::{self, Line};
+    use benchmark_macro::traced;
+
+    struct Secret;
+
+    #[test]
+    #[allow(clippy::needless_borrow)] // The borrow picks the trait, as in the generated code
+    fn test_values() {
+        assert_eq!((&Value(&"hello")).render(), "\"hello\"");
+        assert_eq!((&Value(&vec![1, 2])).render(), "[1, 2]");
+        assert_eq!((&Value(&Secret)).render(), "_");
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn test_errors() {
+        let failed: Result<u8, String> = Err(String::from("timeout"));
+        let succeeded: Result<u8, String> = Ok(1);
+
+        let opaque: Result<u8, Secret> = Err(Secret);
+
+        assert_eq!((&&&Probe(&failed)).error(), Some(String::from("\"timeout\"")));
+        assert_eq!((&&&Probe(&succeeded)).error(), None);
+        assert_eq!((&&&Probe(&opaque)).error(), Some(String::from("_")));
+        assert_eq!((&&&Probe(&42)).error(), None);
+    }
+
+    #[traced(skip(password), level = "debug")]
+    fn login(user: &str, password: &str, _secret: Secret) -> Result<usize, String> {
+        if password.is_empty() {
+            return Err(format!("empty password for {}", user));
+        }
+        let length = password.parse::<usize>().map_err(|error| error.to_string())?;
+
+        Ok(length)
+    }
+
+    #[traced]
+    async fn fetch(id: u32) -> u32 {
+        id * 2
+    }
+
+    struct Refused;
+
+    #[traced]
+    fn connect(port: u16) -> Result<(), Refused> {
+        if port == 0 { Err(Refused) } else { Ok(()) }
+    }
+
+    struct Store(u32);
+
+    impl Store {
+        #[traced(level = "trace")]
+        fn get(&self, key: u32) -> u32 {
+            self.0 + key
+        }
+    }
+
+    #[test]
+    fn test_traced() {
+        assert_eq!(login("ana", "12", Secret), Ok(12));
+        assert_eq!(login("ana", "", Secret), Err(String::from("empty password for ana")));
+        assert!(login("ana", "abc", Secret).is_err());
+        assert_eq!(Store(1).get(2), 3);
+    }
+
+    /// Enter and exit lines of the calls of `name` whose `field` was `value`, made by the current test.
+    fn calls(name: &str, field: &str, value: &str) -> Vec<Line> {
+        let thread = std::thread::current().name().map(String::from);
+        let mut inside = false;
+        capture::lines(|line| line.target == module_path!() && line.thread == thread).into_iter()
+            .filter(|line| {
+                if line.message == format!("enter {}", name) {
+                    inside = line.field(field) == Some(value);
+                    inside
+                } else if inside && line.field("duration_ns").is_some() {
+                    inside = false;
+                    true
+                } else {
+                    false
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_traced_lines() {
+        assert_eq!(login("bea", "3", Secret), Ok(3));
+        let lines = calls("login", "user", "\"bea\"");
+        assert_eq!(lines.len(), 2);
+
+        assert_eq!(lines[0].level, Level::Debug);
+        assert_eq!(lines[0].message, "enter login");
+        assert_eq!(lines[0].field("user"), Some("\"bea\""));
+        assert_eq!(lines[0].field("_secret"), Some("_"));
+        assert_eq!(lines[0].field("password"), None);
+
+        assert_eq!(lines[1].level, Level::Debug);
+        assert!(lines[1].message.starts_with("exit login after "));
+        assert_eq!(lines[1].field("error"), None);
+    }
+
+    #[test]
+    fn test_traced_errors() {
+        assert!(login("cid", "", Secret).is_err());
+        let lines = calls("login", "user", "\"cid\"");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].level, Level::Error);
+        assert!(lines[1].message.starts_with("login failed after "));
+        assert_eq!(lines[1].field("error"), Some("\"empty password for cid\""));
+
+        assert!(connect(0).is_err());
+        let lines = calls("connect", "port", "0");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].level, Level::Error);
+        assert_eq!(lines[1].field("error"), Some("_"));
+    }
+
+    #[test]
+    fn test_traced_async() {
+        use std::future::Future;
+
+        let future = fetch(21);
+        assert!(calls("fetch", "id", "21").is_empty()); // Entered when polled, not when called
+
+        let mut future = std::pin::pin!(future);
+        let waker = std::task::Waker::noop();
+        let mut context = std::task::Context::from_waker(waker);
+
+        assert_eq!(future.as_mut().poll(&mut context), std::task::Poll::Ready(42));
+        let lines = calls("fetch", "id", "21");
+        assert_eq!(lines.len(), 2);
+
+        assert_eq!(lines[0].level, Level::Info);
+        assert_eq!(lines[0].message, "enter fetch");
+
+        assert_eq!(lines[1].level, Level::Info);
+        assert!(lines[1].message.starts_with("exit fetch after "));
+        assert_eq!(lines[1].field("error"), None);
+    }
+}